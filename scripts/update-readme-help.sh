@@ -22,11 +22,11 @@ $ vfy
 '"$HELP_OUTPUT"'
 ```'
 
-# Replace everything between the first ``` and ``` in the README.
+# Replace the ``` block that starts with "$ vfy" in the README.
 # Use perl since the replacement contains newlines and special characters.
 REPLACEMENT="$REPLACEMENT" perl -0777 -i -pe '
     my $r = $ENV{REPLACEMENT};
-    s/^```\n.*?^```/$r/ms;
+    s/^```\n\$ vfy\n.*?^```/$r/ms;
 ' "$README"
 
 echo "README.md updated."
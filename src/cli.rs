@@ -1,11 +1,19 @@
-use clap::Parser;
+use clap::{ArgAction, CommandFactory, Parser, ValueEnum};
+use std::ffi::OsString;
 use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::compare::OrigCache;
+use crate::manifest::Manifest;
+
+use crate::policy::{Category, Policy};
 
 #[derive(Parser, Debug)]
 #[command(
     name = "vfy",
     about = "Verify backup integrity by comparing directory trees. By default, only compares file sizes.",
     arg_required_else_help = true,
+    args_override_self = true,
     after_help = "\
 WARNING: By default, it only compares by file size; files themselves are not read.
 WARNING: Only officially supported on Linux, but seems to work on Windows/Mac.
@@ -29,18 +37,181 @@ Output prefixes (grep-friendly):
   EXTRA-SYMLINK:                 Symlink in backup not in original
   EXTRA-SPECIAL:                 Extra special file in backup not in original
   EXTRA-ERROR:                   Extra something (that errored) in backup not in original
-  DIFFERENT-FILE [reason]:       File differs (reason: first mismatch of SIZE, SAMPLE, HASH)
+  DIFFERENT-FILE [reason]:       File differs (reason: first mismatch of SIZE, SAMPLE, HASH;
+                                 SIZE may be followed by TRUNCATED, GROWN, APPENDED, MISMATCH)
+  ALLOWED-GROWTH:                File grew since the backup, allowed by --allow-growth
+  ZEROED:                        Backup file has zero-filled ranges the original doesn't (--check-zeroed)
+  CHANGED-DURING-VERIFY:         File changed while it was compared, so it was skipped (--retry-changed)
+  DIFF-LOCATION:                 Where a different file differs (--locate-diff)
+  DIFF-CHUNKS:                   Which chunks of a different file differ (--chunk-diff)
   FILE-DIR-MISMATCH:             One side is a file, the other is a directory
   DIFFERENT-SYMLINK-TARGET:      Both sides are symlinks but point to different targets
   DIFFERENT-SYMLINK-STATUS:      One side is a symlink, the other is not
   SPECIAL-FILE:                  Entry is a device, FIFO, socket, etc.
-  SYMLINK-SKIPPED:               Symlink skipped (use --follow to compare resolved content)
-  DANGLING-SYMLINK:              Symlink target does not exist (with --follow)
+  SYMLINK-SKIPPED:               Symlink skipped (use --follow to compare resolved content,
+                                 or the reason a follow policy didn't follow it)
+  DANGLING-SYMLINK:              Symlink target does not exist (with --follow or --check-dangling)
+  SYMLINK-CYCLE:                 Symlink to a directory it is inside of, not descended into (with --follow)
+  SYMLINK-OUTSIDE-BACKUP:        Backup symlink resolves outside the backup, unlike the original's (with --follow)
+  SYMLINK-INTO-ORIGINAL:         Backup symlink resolves into the original, counted as an error (with --follow)
+  VANISHED:                      Entry deleted after its directory was listed (category vanished)
+  NOT-INDEPENDENT:               Original and backup are the same file (hardlink or bind mount; reflinks aren't detected, category not-independent)
   DIFFERENT-FS:                  Different filesystem skipped (--one-filesystem)
   SKIP:                          Entry skipped via --ignore or error/FS/type mismatch between sides
   ERROR:                         I/O or permission error
+  BACKUP N of M:                 Start of the findings for one backup, when verifying several
+  ROOTS N of M:                  Start of one root pair of a --profile with several
+  SAMPLING:                      How files are sampled, including the --seed (with --samples)
+  ABORTED:                       An abort threshold was exceeded (--max-errors, --max-missing-percent, --fail-fast)
   DEBUG:                         Verbose logging (-v dirs, -vv files and hashes)
-  SUMMARY:                       Final counts (not guaranteed to add up to 100%)
+  SUMMARY:                       Final counts (not guaranteed to add up to 100%). With several
+                                 backups, one per backup and a combined one (all backups).
+                                 With several root pairs, one per pair and (all roots)
+
+Exit codes:
+  0    No failing categories found (see exit-code policy below)
+  1    A failing category was found (or a bitmask of them, with --exit-bitmask)
+  2    Invalid arguments
+  3    Aborted early because a threshold was exceeded; verification is INCOMPLETE
+  130  Interrupted (Ctrl-C); verification is INCOMPLETE
+
+Exit-code policy (--fail-on, --warn-on, --allow take comma-separated categories):
+  Categories: missing, different, extras, special, errors, dangling, skipped,
+  cycles, vanished, not-independent
+  By default every category fails the run except skipped, which is ignored.
+  --warn-on categories print a WARNING after the summary but exit 0.
+  Dangling symlinks and symlink cycles are included in the summary's Errors
+  count, but the errors category only covers the other errors.
+  Vanished entries were deleted after their directory was listed, which is
+  normal on busy trees; use --allow vanished to not fail because of them.
+  Not-independent files are the same file in the original and the backup,
+  so the backup doesn't protect them; use --allow not-independent for trees
+  that are hardlinked on purpose.
+  With --exit-bitmask, the exit code is the sum of the failing categories that
+  occurred: missing=1, different=4, extras=8, special=16, errors=32,
+  skipped=64, with dangling, cycles and vanished sharing 32 with errors, and
+  not-independent sharing 4 with different.
+  No combination is 2 or 3, which stay reserved for invalid arguments and
+  aborts, or above 125, so it can't be confused with 130 (interrupted), but
+  only trust it if COMPARISON FINISHED! was printed.
+
+Classifying against a previous backup (--previous DIR):
+  DIFFERENT-FILE, DIFFERENT-SYMLINK-TARGET and MISSING-* lines get a note
+  comparing the original with the same path in DIR, using the same checks:
+    (changed at source)      The original no longer matches DIR: expected.
+    (corrupted in backup)    The original still matches DIR, so the backup
+                             should have matched too: investigate!
+    (new since last backup)  DIR doesn't have the entry.
+  The notes don't change the exit code.
+
+Profiles (--profile NAME, read from --config FILE):
+  A TOML file with one [profiles.NAME] table per job. Keys are long option
+  names (samples = 3, follow = true, ignore = [\"...\"], verbose = 2), plus
+  the roots: either original and backups, or one [[profiles.NAME.roots]]
+  table per root pair, each verified in turn and summarized as ROOTS N of M.
+  A roots table may have its own ignore list; other ignores only apply to
+  the root pairs they are in.
+  Options on the command line replace the profile's (verbose = 2 with -v is
+  -v), list options like --ignore add to it, and roots on the command line
+  replace the profile's. --no-FLAG turns off a flag such as follow = true.
+
+Remapping subtrees (--map ORIG_SUBPATH=BACKUP_SUBPATH):
+  Paths are relative to the roots, e.g. --map home/alice/docs=users/alice/documents
+  compares original/home/alice/docs against backup/users/alice/documents.
+  Both sides keep their other entries: a backup/home/alice/docs is EXTRA, and
+  if BACKUP_SUBPATH doesn't exist, ORIG_SUBPATH is MISSING. Directories that
+  only lead to a mapped path, like home/alice or users/alice when the other
+  side doesn't have them, aren't MISSING or EXTRA themselves, but their other
+  entries are. Give --map once per subtree; rules may be nested.
+
+Sampling (--samples N, --sample-size BYTES, --sample-strategy STRATEGY):
+  random      N uniformly random offsets (default)
+  edges       The first and last block, then random offsets: catches
+              truncated tails and zeroed headers
+  stratified  One random offset in each of N equal parts of the file
+  scaled      N random offsets per started GiB, so big files get more
+  Offsets are derived from --seed and the file's path, so a file is sampled
+  at the same offsets in every backup and on every run with the same seed.
+  The sampling that is used, including the seed (which is random unless
+  given), is shown before the comparison starts and again in the summary, so
+  a SAMPLE mismatch can be reproduced with --seed.
+
+Hashing (--hash-mode MODE, --hash-buffer BYTES, --hash-threads N):
+  read  Read files with a buffer of --hash-buffer bytes (default 1 MiB). Safe
+        for files that change, and often faster on FUSE and network
+        filesystems (default)
+  mmap  Memory-map files. Fast on local disks, but if another process
+        truncates a file while it is mapped, vfy dies with SIGBUS
+  auto  Memory-map files, and read them if that fails. Has the same risk as mmap
+  --hash-threads limits the threads that hash a file (default: one per CPU).
+  All modes give the same hashes.
+
+Files changed during verification (--retry-changed N):
+  After comparing two files, both are stat'ed again. If the size, modification
+  time, change time or inode of either changed, the comparison can't be
+  trusted, so the files are compared again, up to N times (default 0), and
+  then reported as
+    CHANGED-DURING-VERIFY: [path] (original changed)
+  These are counted as skipped, so they don't fail the run unless
+  --fail-on skipped. Files deleted meanwhile are VANISHED instead. Without
+  --samples, --all or another option that reads files, only the sizes are
+  compared, so the files aren't stat'ed again.
+
+Size differences (--check-prefix):
+  DIFFERENT-FILE [SIZE] lines show both sizes. With --check-prefix, the
+  shorter file is read and compared with the start of the longer one:
+    TRUNCATED  The backup is the start of the original, e.g. an interrupted copy
+    GROWN      The same, but the original was modified after the backup, so it
+               probably grew since it was backed up (a log file, say)
+    APPENDED   The original is the start of the backup
+    MISMATCH   Neither file is the start of the other
+  e.g. DIFFERENT-FILE [SIZE, TRUNCATED]: [path] (original 4096 bytes, backup 1024 bytes)
+
+Growing files (--allow-growth PATTERN):
+  A file matching PATTERN whose backup is shorter than the original counts as
+  similar, if the backup matches the start of the original using the same
+  checks as whole files (--samples, --all; with neither, the backup and the
+  start of the original are hashed). It is reported as
+    ALLOWED-GROWTH: [path] (original 4096 bytes, backup 1024 bytes)
+  Other size changes are still differences. A PATTERN without '/' matches file
+  names, one with '/' matches paths relative to the root. '*' matches any
+  characters except '/', and '?' matches one. Give --allow-growth once per
+  pattern.
+
+Zeroed blocks (--check-zeroed, --zeroed-min BYTES):
+  Unclean shutdowns can leave backup files with their size intact but runs of
+  zero bytes inside. --check-zeroed reads each backup file (unless --all found
+  it equal) and reports runs of at least --zeroed-min bytes (default 64 KiB)
+  where the original isn't zero, e.g.
+    ZEROED: [path] 1 zero-filled ranges (65536 bytes): 131072..196608
+  The original is only read where the backup is zero. With --manifest, its
+  stored chunk hashes are used instead. If the original can't be read,
+  compressed, encrypted and media files (.gz, .zip, .jpg, .mp4, .gpg, ...)
+  are still reported, since they practically never contain such runs. A file
+  found only by this check is counted as different.
+
+Locating differences (--locate-diff, --locate-limit BYTES):
+  After a SAMPLE or HASH mismatch, both files are read again from the start
+  and compared byte by byte, e.g.
+    DIFF-LOCATION: [path] first difference at byte 4096, 2 differing ranges (8192 bytes)
+  Reading stops after --locate-limit bytes (default 1 GiB), and the counts
+  then only cover that part of the file.
+
+Chunk hashes and manifests (--chunk-diff, --chunk-size, --manifest, --write-manifest):
+  --chunk-diff hashes both files in chunks of --chunk-size bytes (default
+  1 MiB) after a SAMPLE or HASH mismatch, and reports the differing ranges:
+    DIFF-CHUNKS: [path] 2 of 3 chunks of 1048576 bytes differ: 0..1048576, 2097152..3000000
+  --write-manifest FILE (with --all) stores the hash and the chunk hashes of
+  each original file. With --manifest FILE, original files whose size and
+  modification time haven't changed since aren't read again: their stored
+  hashes are used for --all and, if written with the same --chunk-size, for
+  --chunk-diff. Given both, the new manifest keeps the stored entries of
+  unchanged files.
+
+Comparing two files:
+  ORIGINAL and BACKUP may also be files, which are compared like files inside
+  a tree (by size, then --samples, then --all). A file and a directory are
+  reported as FILE-DIR-MISMATCH. A symlink to a file needs --follow.
 
 Symlink handling with --follow:
   When both sides are symlinks with different targets:
@@ -52,16 +223,34 @@ Symlink handling with --follow:
     - Reports original as MISSING-*, backup symlink as EXTRA-* (or vice-versa)
     - Does NOT compare contents (structural failure means no backup exists)
 
+  With --normalize-symlinks, targets are compared by where they point:
+  relative targets are resolved lexically from the symlink's directory, and
+  absolute targets inside the original are rebased onto the backup. Targets
+  that still differ are shown both raw and normalized (relative to the root).
+
+  Backup symlinks are also checked for where they resolve to. If that's in
+  the original, the original is compared with itself, so it's an error.
+
   Rationale: A symlink replacing a directory is a structural failure--the backup
   tree doesn't contain the actual data. Two symlinks with different targets is
   a metadata difference--the resolved data may still be equivalent."
 )]
 pub struct Cli {
-    /// Original directory
-    pub original: PathBuf,
+    /// Original directory (or file)
+    #[arg(required_unless_present = "profile")]
+    pub original: Option<PathBuf>,
 
-    /// Backup directory
-    pub backup: PathBuf,
+    /// Backup directory or file (give several to verify multiple copies in one run).
+    #[arg(required_unless_present = "profile", value_name = "BACKUP")]
+    pub backups: Vec<PathBuf>,
+
+    /// Run the named profile from the config file (see below)
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Config file with profiles [default: ~/.config/vfy/profiles.toml]
+    #[arg(long, value_name = "FILE", requires = "profile")]
+    pub config: Option<PathBuf>,
 
     /// Verbose output (-v for dirs, -vv for files, hashes with --all, see below)
     #[arg(short, long, action = clap::ArgAction::Count)]
@@ -71,22 +260,185 @@ pub struct Cli {
     #[arg(short, long, default_value_t = 0)]
     pub samples: u32,
 
+    /// Length of each sample in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 32, value_parser = clap::value_parser!(u64).range(1..))]
+    pub sample_size: u64,
+
+    /// Where samples are taken from (see below)
+    #[arg(long, value_name = "STRATEGY", value_enum, default_value_t = SampleStrategy::Random)]
+    pub sample_strategy: SampleStrategy,
+
+    /// Seed for the sample offsets (default: random, shown in the summary)
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
     /// Full BLAKE3 hash comparison
     #[arg(short, long)]
     pub all: bool,
 
+    /// How files are read for hashing (see below)
+    #[arg(long, value_name = "MODE", value_enum, default_value_t = HashMode::Read)]
+    pub hash_mode: HashMode,
+
+    /// Read buffer size for --hash-mode read
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 20, value_parser = clap::value_parser!(u64).range(1..))]
+    pub hash_buffer: u64,
+
+    /// Threads used to hash each file (default: one per CPU)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub hash_threads: Option<u32>,
+
+    /// Compare files that changed during verification again, up to N times (see below)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retry_changed: u32,
+
+    /// For files of different sizes, check whether the shorter is the start of the longer (see below)
+    #[arg(long)]
+    pub check_prefix: bool,
+
+    /// Look for zero-filled ranges in backup files where the original has data (see below)
+    #[arg(long)]
+    pub check_zeroed: bool,
+
+    /// Shortest run of zero bytes that --check-zeroed reports
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 16, value_parser = clap::value_parser!(u64).range(1..), requires = "check_zeroed")]
+    pub zeroed_min: u64,
+
+    /// After a SAMPLE or HASH mismatch, find where the files differ (see below)
+    #[arg(long)]
+    pub locate_diff: bool,
+
+    /// Stop --locate-diff after this many bytes of each file
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 30, requires = "locate_diff")]
+    pub locate_limit: u64,
+
+    /// After a SAMPLE or HASH mismatch, compare BLAKE3 hashes of chunks of the files (see below)
+    #[arg(long)]
+    pub chunk_diff: bool,
+
+    /// Chunk size for --chunk-diff and --write-manifest
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 20, value_parser = clap::value_parser!(u64).range(1..))]
+    pub chunk_size: u64,
+
+    /// Use the original's hashes stored in FILE for files that haven't changed since
+    #[arg(long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
+    /// Store the original's hashes in FILE, for use with --manifest
+    #[arg(long, value_name = "FILE", requires = "all")]
+    pub write_manifest: Option<PathBuf>,
+
     /// Compare symlinked-to contents (symlink target paths are always compared, even without --follow)
     #[arg(short, long)]
     pub follow: bool,
 
+    /// With --follow, only follow symlinks that resolve to within their root
+    #[arg(long, requires = "follow")]
+    pub follow_within_root: bool,
+
+    /// With --follow, only follow symlinks to files, not directories
+    #[arg(long, requires = "follow")]
+    pub follow_files_only: bool,
+
+    /// With --follow, follow symlinks at most N deep (a symlink inside a followed directory is one deeper)
+    #[arg(long, value_name = "N", requires = "follow")]
+    pub max_follow_depth: Option<usize>,
+
     /// Stay on one filesystem (only supported on Unix-like OSes)
     #[cfg(unix)]
     #[arg(short = 'o', long)]
     pub one_filesystem: bool,
 
+    /// Report DANGLING-SYMLINK without --follow, without comparing resolved content
+    #[arg(long)]
+    pub check_dangling: bool,
+
+    /// Compare symlink targets by where they point in each tree, not byte-for-byte (see below)
+    #[arg(long)]
+    pub normalize_symlinks: bool,
+
     /// Ignore one directory or file. Must exist. Ignoring one side also ignores the other.
     #[arg(short, long, verbatim_doc_comment)] // verbatim so it doesn't strip the period!
     pub ignore: Vec<PathBuf>,
+
+    /// Files matching PATTERN may have grown since the backup, e.g. '*.log' (see below)
+    #[arg(long, value_name = "PATTERN")]
+    pub allow_growth: Vec<String>,
+
+    /// Compare ORIG_SUBPATH in the original against BACKUP_SUBPATH in the backup (see below)
+    #[arg(long, value_name = "ORIG=BACKUP")]
+    pub map: Vec<String>,
+
+    /// Previous backup of the original, to classify each DIFFERENT/MISSING entry (see below)
+    #[arg(long, value_name = "DIR")]
+    pub previous: Option<PathBuf>,
+
+    /// Backup may contain entries not in the original: don't descend into, report, or count them
+    #[arg(long)]
+    pub superset: bool,
+
+    /// Abort once more than N errors have been found
+    #[arg(long, value_name = "N")]
+    pub max_errors: Option<u64>,
+
+    /// Abort once more than PERCENT of original items are missing (checked after 100 items)
+    #[arg(long, value_name = "PERCENT")]
+    pub max_missing_percent: Option<f64>,
+
+    /// Abort at the first finding in a failing category (see --fail-on)
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Categories that fail the run (see exit-code policy below)
+    #[arg(long, value_name = "CATEGORY", value_delimiter = ',')]
+    pub fail_on: Vec<Category>,
+
+    /// Categories that only print a warning after the summary
+    #[arg(long, value_name = "CATEGORY", value_delimiter = ',')]
+    pub warn_on: Vec<Category>,
+
+    /// Categories that never affect the exit code
+    #[arg(long, value_name = "CATEGORY", value_delimiter = ',')]
+    pub allow: Vec<Category>,
+
+    /// Exit with a bitmask of the failing categories that occurred instead of 1
+    #[arg(long)]
+    pub exit_bitmask: bool,
+}
+
+/// How --samples picks the offsets to compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SampleStrategy {
+    // Uniformly random offsets.
+    Random,
+    // The first and last block, then random offsets.
+    Edges,
+    // One random offset in each of N equal parts of the file.
+    Stratified,
+    // N random offsets per started GiB of file size.
+    Scaled,
+}
+
+impl SampleStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            SampleStrategy::Random => "random",
+            SampleStrategy::Edges => "edges",
+            SampleStrategy::Stratified => "stratified",
+            SampleStrategy::Scaled => "scaled",
+        }
+    }
+}
+
+/// How files are read for hashing (--hash-mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HashMode {
+    // Memory-map, and read if that fails.
+    Auto,
+    // Memory-map only.
+    Mmap,
+    // Read with a buffer of --hash-buffer bytes.
+    Read,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -96,30 +448,120 @@ pub enum Verbosity {
     Files,
 }
 
+/// One backup root given on the command line.
+#[derive(Clone)]
+pub struct BackupRoot {
+    pub path: PathBuf,
+    /// Device ID of the backup root (for --one-filesystem).
+    #[cfg(unix)]
+    pub device: Option<u64>,
+}
+
+/// One --map rule. Both paths are relative to their root.
+#[derive(Clone)]
+pub struct PathMap {
+    pub orig: PathBuf,
+    pub backup: PathBuf,
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub original: PathBuf,
+    /// The backup being compared. With several backups, each pass gets its own
+    /// Config from for_backup().
     pub backup: PathBuf,
+    /// All backups given on the command line, in order.
+    pub backups: Vec<BackupRoot>,
     pub verbosity: Verbosity,
     pub samples: u32,
+    pub sample_size: u64,
+    pub sample_strategy: SampleStrategy,
+    /// Seed for the sample offsets, from --seed or chosen at random.
+    pub seed: u64,
     pub all: bool,
+    pub hash_mode: HashMode,
+    pub hash_buffer: usize,
+    /// Thread pool for hashing, if --hash-threads limits it.
+    pub hash_pool: Option<Arc<rayon::ThreadPool>>,
+    /// How often to compare a file that changed during verification again.
+    pub retry_changed: u32,
+    /// Classify size differences by reading the shorter file (--check-prefix).
+    pub check_prefix: bool,
+    /// Look for zero-filled ranges in backup files (--check-zeroed).
+    pub check_zeroed: bool,
+    pub zeroed_min: u64,
+    /// Locate the differing bytes of different files (--locate-diff).
+    pub locate_diff: bool,
+    pub locate_limit: u64,
+    /// Compare chunk hashes of different files (--chunk-diff).
+    pub chunk_diff: bool,
+    pub chunk_size: u64,
+    /// Stored hashes of the original (--manifest).
+    pub manifest: Option<Arc<Manifest>>,
+    /// Hashes of the original collected for --write-manifest, and where to
+    /// write them.
+    pub manifest_out: Option<(PathBuf, Arc<Mutex<Manifest>>)>,
     pub follow: bool,
+    /// Follow policies (--follow-within-root, --follow-files-only, --max-follow-depth).
+    pub follow_within_root: bool,
+    pub follow_files_only: bool,
+    pub max_follow_depth: Option<usize>,
+    /// Check symlink targets exist without following them (--check-dangling).
+    pub check_dangling: bool,
+    /// Compare symlink targets after normalizing them (--normalize-symlinks).
+    pub normalize_symlinks: bool,
     pub ignore: Vec<PathBuf>,
+    /// Files that may have grown since the backup (--allow-growth).
+    pub allow_growth: Vec<String>,
+    /// Subtrees stored under a different path in the backup (--map).
+    pub map: Vec<PathMap>,
+    /// Previous backup to classify differences against (--previous).
+    pub previous: Option<PathBuf>,
+    /// Backup is allowed to be a superset of the original (--superset).
+    pub superset: bool,
+    pub max_errors: Option<u64>,
+    pub max_missing_percent: Option<f64>,
+    pub fail_fast: bool,
+    pub policy: Policy,
+    pub exit_bitmask: bool,
     /// Device ID of the original root directory (for --one-filesystem). Set to enforce staying on the same filesystem.
     #[cfg(unix)]
     pub original_device: Option<u64>,
     /// Device ID of the backup root directory (for --one-filesystem). Set to enforce staying on the same filesystem.
     #[cfg(unix)]
     pub backup_device: Option<u64>,
+    /// Shared between the passes when there are several backups, so the
+    /// original only gets read once.
+    pub orig_cache: Option<Arc<OrigCache>>,
 }
 
 impl Config {
     pub fn from_cli(cli: Cli) -> Result<Self, String> {
-        let original = cli.original.canonicalize().map_err(|e| {
-            format!("Cannot resolve original directory {:?}: {}", cli.original, e)
-        })?;
-        let backup = cli.backup.canonicalize().map_err(|e| {
-            format!("Cannot resolve backup directory {:?}: {}", cli.backup, e)
+        let cli_original = cli.original.as_ref().ok_or("No original directory given")?;
+        if cli.backups.is_empty() {
+            return Err("No backup directory given".to_string());
+        }
+        let original = cli_original.canonicalize().map_err(|e| {
+            format!("Cannot resolve original directory {:?}: {}", cli_original, e)
         })?;
+        let mut backups_canonical = Vec::new();
+        for b in &cli.backups {
+            backups_canonical.push(b.canonicalize().map_err(|e| {
+                format!("Cannot resolve backup directory {:?}: {}", b, e)
+            })?);
+        }
+
+        // Symlinks to directories as roots are always resolved, but like
+        // symlinks inside the tree, a symlink to a file is only followed with
+        // --follow.
+        if !cli.follow {
+            for typed in std::iter::once(cli_original).chain(&cli.backups) {
+                let is_symlink = typed.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink());
+                if is_symlink && !typed.is_dir() {
+                    return Err(format!("{:?} is a symlink to a file, use --follow to compare its target", typed));
+                }
+            }
+        }
 
         let verbosity = match cli.verbose {
             0 => Verbosity::Quiet,
@@ -128,6 +570,65 @@ impl Config {
             n => return Err(format!("-v can be specified at most twice, but was specified {} times", n)),
         };
 
+        if let Some(pct) = cli.max_missing_percent {
+            if !(0.0..=100.0).contains(&pct) {
+                return Err(format!("--max-missing-percent must be between 0 and 100, but was {}", pct));
+            }
+        }
+
+        let hash_pool = match cli.hash_threads {
+            Some(n) => Some(Arc::new(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(n as usize)
+                    .build()
+                    .map_err(|e| format!("Cannot start {} hashing threads: {}", n, e))?,
+            )),
+            None => None,
+        };
+
+        let manifest = match &cli.manifest {
+            Some(p) => Some(Arc::new(Manifest::load(p)?)),
+            None => None,
+        };
+
+        let previous = match &cli.previous {
+            Some(p) => {
+                let canonical = p.canonicalize().map_err(|e| {
+                    format!("Cannot resolve previous backup directory {:?}: {}", p, e)
+                })?;
+                if canonical.is_dir() != original.is_dir() {
+                    let kind = if original.is_dir() { "directory" } else { "file" };
+                    return Err(format!("{:?} is not a {} like the original", p, kind));
+                }
+                Some(canonical)
+            }
+            None => None,
+        };
+
+        let mut map: Vec<PathMap> = Vec::new();
+        for rule in &cli.map {
+            let parsed = rule.split_once('=').and_then(|(o, b)| Some(PathMap {
+                orig: relative_subpath(o)?,
+                backup: relative_subpath(b)?,
+            }));
+            let Some(parsed) = parsed else {
+                return Err(format!(
+                    "--map expects ORIG_SUBPATH=BACKUP_SUBPATH with relative, non-empty paths, but was {:?}",
+                    rule
+                ));
+            };
+            if map.iter().any(|m| m.orig == parsed.orig) {
+                return Err(format!("--map given twice for {:?}", parsed.orig));
+            }
+            map.push(parsed);
+        }
+
+        if let Some(pattern) = cli.allow_growth.iter().find(|p| p.is_empty() || p.starts_with('/')) {
+            return Err(format!("--allow-growth expects a non-empty, relative pattern, but was {:?}", pattern));
+        }
+
+        let policy = Policy::from_lists(&cli.fail_on, &cli.warn_on, &cli.allow)?;
+
         // Validate --ignore paths: must exist and be within original or backup tree.
         //
         // We canonicalize the original and backup roots because:
@@ -201,8 +702,9 @@ impl Config {
             if p.is_absolute() { normalize_path(p) } else { normalize_path(&cwd.join(p)) }
         };
 
-        let orig_as_typed = make_absolute_and_normalized(&cli.original);
-        let backup_as_typed = make_absolute_and_normalized(&cli.backup);
+        let orig_as_typed = make_absolute_and_normalized(cli_original);
+        let backups_as_typed: Vec<PathBuf> =
+            cli.backups.iter().map(|b| make_absolute_and_normalized(b)).collect();
 
         let mut ignore = Vec::new();
 
@@ -232,16 +734,24 @@ impl Config {
             // 2. The absolute ignore path starts with the canonicalized original path.
             } else if let Ok(suffix) = normed.strip_prefix(&original) {
                 original.join(suffix)
-            // 3. The absolute ignore path starts with the user-specified backup path.
-            } else if let Ok(suffix) = normed.strip_prefix(&backup_as_typed) {
-                backup.join(suffix)
-            // 4. The absolute ignore path starts with the canonicalized backup path.
-            } else if let Ok(suffix) = normed.strip_prefix(&backup) {
-                backup.join(suffix)
+            // 3. The absolute ignore path starts with a user-specified backup path.
+            // 4. The absolute ignore path starts with a canonicalized backup path.
+            } else if let Some(stored) = backups_as_typed.iter().zip(&backups_canonical).find_map(
+                |(as_typed, canonical)| {
+                    normed
+                        .strip_prefix(as_typed)
+                        .or_else(|_| normed.strip_prefix(canonical))
+                        .ok()
+                        .map(|suffix| canonical.join(suffix))
+                },
+            ) {
+                stored
             } else {
                 return Err(format!(
                     "Ignore path {:?} is not within the original ({:?}) or backup ({:?}) directory",
-                    normed, original, backup
+                    normed,
+                    original,
+                    backups_canonical.iter().map(|b| b.as_path()).collect::<Vec<_>>()
                 ));
             };
             ignore.push(stored);
@@ -249,36 +759,169 @@ impl Config {
 
         // Get device IDs for --one-filesystem check
         #[cfg(unix)]
-        let (original_device, backup_device) = if cli.one_filesystem {
+        let original_device = if cli.one_filesystem {
             use std::os::unix::fs::MetadataExt;
             let orig_dev = std::fs::metadata(&original)
                 .map_err(|e| format!("Cannot stat original directory {:?}: {}", original, e))?
                 .dev();
-            let backup_dev = std::fs::metadata(&backup)
-                .map_err(|e| format!("Cannot stat backup directory {:?}: {}", backup, e))?
-                .dev();
-            (Some(orig_dev), Some(backup_dev))
+            Some(orig_dev)
         } else {
-            (None, None)
+            None
         };
 
+        let mut backups = Vec::new();
+        for path in backups_canonical {
+            #[cfg(unix)]
+            let device = if cli.one_filesystem {
+                use std::os::unix::fs::MetadataExt;
+                let backup_dev = std::fs::metadata(&path)
+                    .map_err(|e| format!("Cannot stat backup directory {:?}: {}", path, e))?
+                    .dev();
+                Some(backup_dev)
+            } else {
+                None
+            };
+            backups.push(BackupRoot {
+                path,
+                #[cfg(unix)]
+                device,
+            });
+        }
+
         Ok(Config {
             original,
-            backup,
+            backup: backups[0].path.clone(),
             verbosity,
             samples: cli.samples,
+            sample_size: cli.sample_size,
+            sample_strategy: cli.sample_strategy,
+            seed: cli.seed.unwrap_or_else(rand::random),
             all: cli.all,
+            hash_mode: cli.hash_mode,
+            hash_buffer: cli.hash_buffer.try_into().unwrap_or(usize::MAX),
+            hash_pool,
+            retry_changed: cli.retry_changed,
+            check_prefix: cli.check_prefix,
+            check_zeroed: cli.check_zeroed,
+            zeroed_min: cli.zeroed_min,
+            locate_diff: cli.locate_diff,
+            locate_limit: cli.locate_limit,
+            chunk_diff: cli.chunk_diff,
+            chunk_size: cli.chunk_size,
+            manifest,
+            manifest_out: cli
+                .write_manifest
+                .map(|p| (p, Arc::new(Mutex::new(Manifest::new(cli.chunk_size))))),
             follow: cli.follow,
+            follow_within_root: cli.follow_within_root,
+            follow_files_only: cli.follow_files_only,
+            max_follow_depth: cli.max_follow_depth,
+            check_dangling: cli.check_dangling,
+            normalize_symlinks: cli.normalize_symlinks,
             ignore,
+            allow_growth: cli.allow_growth,
+            map,
+            previous,
+            superset: cli.superset,
+            max_errors: cli.max_errors,
+            max_missing_percent: cli.max_missing_percent,
+            fail_fast: cli.fail_fast,
+            policy,
+            exit_bitmask: cli.exit_bitmask,
             #[cfg(unix)]
             original_device,
             #[cfg(unix)]
-            backup_device,
+            backup_device: backups[0].device,
+            backups,
+            orig_cache: None,
         })
     }
+
+    /// Description of the sampling for the summary, if --samples is used.
+    pub fn sampling_description(&self) -> Option<String> {
+        if self.samples == 0 {
+            return None;
+        }
+        let per = match self.sample_strategy {
+            SampleStrategy::Scaled => "per started GiB of each file",
+            _ => "per file",
+        };
+        Some(format!(
+            "{} samples of {} bytes {} ({}), --seed {}",
+            self.samples,
+            self.sample_size,
+            per,
+            self.sample_strategy.name(),
+            self.seed
+        ))
+    }
+
+    /// Config for the pass that compares the original against backup `index`.
+    pub fn for_backup(&self, index: usize) -> Config {
+        let root = &self.backups[index];
+        Config {
+            backup: root.path.clone(),
+            #[cfg(unix)]
+            backup_device: root.device,
+            ..self.clone()
+        }
+    }
+}
+
+/// Remove every `--no-FLAG` from a command line, together with each `--FLAG`
+/// (or its short form) that it turns off, wherever it appears. Returns the
+/// remaining arguments and the ids of the flags turned off, so that a profile
+/// setting them can be overridden too. Only flags without a value can be
+/// turned off; other `--no-...` arguments are left for clap to reject.
+pub fn apply_negations(args: Vec<OsString>) -> (Vec<OsString>, Vec<String>) {
+    let command = Cli::command();
+    let options_end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let flags: Vec<&clap::Arg> = args[..options_end]
+        .iter()
+        .filter_map(|a| a.to_str()?.strip_prefix("--no-"))
+        .filter_map(|long| {
+            command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(long) && matches!(arg.get_action(), ArgAction::SetTrue))
+        })
+        .collect();
+
+    let turned_off = |a: &OsString| {
+        let Some(a) = a.to_str() else { return false };
+        flags.iter().any(|flag| {
+            let long = flag.get_long().unwrap_or_default();
+            a.strip_prefix("--no-") == Some(long)
+                || a.strip_prefix("--") == Some(long)
+                || flag.get_short().is_some_and(|short| a == format!("-{}", short))
+        })
+    };
+
+    let remaining = args
+        .iter()
+        .enumerate()
+        .filter(|&(i, a)| i == 0 || i >= options_end || !turned_off(a))
+        .map(|(_, a)| a.clone())
+        .collect();
+    let negated = flags.iter().map(|flag| flag.get_id().to_string()).collect();
+    (remaining, negated)
+}
+
+/// Parse one side of a --map rule: a relative path inside its root, without
+/// `..`. Returns None if there is nothing left after normalizing.
+fn relative_subpath(s: &str) -> Option<PathBuf> {
+    let path = Path::new(s);
+    if path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+        return None;
+    }
+    let normalized = normalize_path(path);
+    if normalized.as_os_str().is_empty() {
+        None
+    } else {
+        Some(normalized)
+    }
 }
 
-fn normalize_path(path: &Path) -> PathBuf {
+pub fn normalize_path(path: &Path) -> PathBuf {
     let mut result = PathBuf::new();
     for component in path.components() {
         match component {
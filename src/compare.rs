@@ -1,20 +1,32 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::fs;
+use std::hash::Hash;
 use std::io::Read;
-use std::io::Seek;
-use std::io::SeekFrom;
-use std::path::Path;
-use rand::Rng;
-
-use crate::cli::{Config, Verbosity};
-use crate::stats::{DiffReasons, Stats};
+#[cfg(not(unix))]
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cli::{normalize_path, Config, HashMode, SampleStrategy, Verbosity};
+use crate::manifest::chunk_hashes;
+use crate::stats::{DiffReasons, SizeChange, Stats};
 
 /// Result of loading metadata for a path.
 #[derive(Debug, Clone)]
 enum Meta {
     Error(String),
     Dangling,
+    /// Listed in its directory, but deleted before it was stat'ed or read.
+    Vanished,
+    /// A followed symlink to a directory that is already on the current path
+    /// (see into_cycle_if_ancestor()).
+    Cycle,
     Special(fs::Metadata),
     File(fs::Metadata),
     Dir(fs::Metadata, Vec<OsString>),
@@ -32,7 +44,7 @@ enum Direction {
 
 impl Meta {
     fn is_error_or_dangling(&self) -> bool {
-        matches!(self, Meta::Error(_) | Meta::Dangling)
+        matches!(self, Meta::Error(_) | Meta::Dangling | Meta::Cycle | Meta::Vanished)
     }
 
     fn is_file_dir_or_symlink(&self) -> bool {
@@ -49,18 +61,18 @@ impl Direction {
             // This is unreachable because dangling symlinks are reported as
             // missing in the call to report() without follow=true, i.e. before
             // we ever try to resolve them.
-            (Direction::Missing, Meta::Dangling) => "MISSING-SYMLINK",
+            (Direction::Missing, Meta::Dangling | Meta::Cycle) => "MISSING-SYMLINK",
             (Direction::Missing, Meta::Special(_)) => "MISSING-SPECIAL",
-            (Direction::Missing, Meta::Error(_)) => "MISSING-ERROR",
+            (Direction::Missing, Meta::Error(_) | Meta::Vanished) => "MISSING-ERROR",
             (Direction::Extra, Meta::File(_)) => "EXTRA-FILE",
             (Direction::Extra, Meta::Dir(_, _)) => "EXTRA-DIR",
             (Direction::Extra, Meta::Symlink(_)) => "EXTRA-SYMLINK",
             // This is unreachable because dangling symlinks are reported as
             // missing in the call to report() without follow=true, i.e. before
             // we ever try to resolve them.
-            (Direction::Extra, Meta::Dangling) => "EXTRA-SYMLINK",
+            (Direction::Extra, Meta::Dangling | Meta::Cycle) => "EXTRA-SYMLINK",
             (Direction::Extra, Meta::Special(_)) => "EXTRA-SPECIAL",
-            (Direction::Extra, Meta::Error(_)) => "EXTRA-ERROR",
+            (Direction::Extra, Meta::Error(_) | Meta::Vanished) => "EXTRA-ERROR",
         }
     }
 
@@ -77,6 +89,15 @@ impl Direction {
             Direction::Extra => stats.inc_backup_items(),
         }
     }
+
+    /// previous_note() for MISSING entries. Extras aren't classified, since
+    /// they have no original to compare with the previous backup.
+    fn previous_note(self, path: &Path, config: &Config, stats: &Stats) -> &'static str {
+        match self {
+            Direction::Missing => previous_note(path, config, stats),
+            Direction::Extra => "",
+        }
+    }
 }
 
 // -- Metadata loading ---------------------------------------------------------
@@ -92,7 +113,13 @@ fn load_meta(path: &Path, follow: bool) -> Meta {
     let meta = if follow {
         match fs::metadata(path) {
             Ok(m) => m,
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Meta::Dangling,
+            // A dangling symlink, unless the symlink itself is gone too.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return match fs::symlink_metadata(path) {
+                    Ok(_) => Meta::Dangling,
+                    Err(_) => Meta::Vanished,
+                };
+            }
             Err(e) => {
                 return Meta::Error(format!("Cannot read metadata for [{}]: {}", path.display(), e));
             }
@@ -100,6 +127,7 @@ fn load_meta(path: &Path, follow: bool) -> Meta {
     } else {
         match fs::symlink_metadata(path) {
             Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Meta::Vanished,
             Err(e) => {
                 return Meta::Error(format!("Cannot read metadata for [{}]: {}", path.display(), e));
             }
@@ -121,6 +149,7 @@ fn load_meta(path: &Path, follow: bool) -> Meta {
                 entries.sort();
                 Meta::Dir(meta, entries)
             }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Meta::Vanished,
             Err(e) => {
                 Meta::Error(format!("Cannot read directory [{}]: {}", path.display(), e))
             }
@@ -152,6 +181,34 @@ fn load_meta(path: &Path, follow: bool) -> Meta {
     }
 }
 
+/// With follow=true, a directory that is also one of the directories on the
+/// way down from `root` to `path` is a symlink back to an ancestor, e.g.
+/// `loop -> ..`. Descending into it would never end, so turn it into
+/// Meta::Cycle.
+///
+/// Only symlinks can cause this, so it's only checked for followed paths.
+/// The ancestors are stat'ed with symlinks followed, since the way down may
+/// itself go through other followed symlinks.
+fn into_cycle_if_ancestor(meta: Meta, path: &Path, root: &Path) -> Meta {
+    #[cfg(unix)]
+    if let Meta::Dir(m, _) = &meta {
+        use std::os::unix::fs::MetadataExt;
+        let id = (m.dev(), m.ino());
+        for ancestor in path.ancestors().skip(1) {
+            if fs::metadata(ancestor).is_ok_and(|a| (a.dev(), a.ino()) == id) {
+                return Meta::Cycle;
+            }
+            if ancestor == root {
+                break;
+            }
+        }
+    }
+    // TODO: Detect cycles on non-Unix OSes (needs file IDs).
+    #[cfg(not(unix))]
+    let _ = (path, root);
+    meta
+}
+
 fn read_dir_entries(dir: &Path) -> Result<Vec<OsString>, std::io::Error> {
     let mut entries = Vec::new();
     for entry in fs::read_dir(dir)? {
@@ -161,6 +218,129 @@ fn read_dir_entries(dir: &Path) -> Result<Vec<OsString>, std::io::Error> {
     Ok(entries)
 }
 
+/// Most entries the metadata cache of OrigCache holds, counting every name of
+/// a cached directory listing. Once full, the metadata of later entries is
+/// looked up again by every pass.
+const ORIG_CACHE_LIMIT: usize = 1 << 21;
+
+/// Original-side results shared by the passes when one original is verified
+/// against several backups, so the original is only stat'ed, listed and hashed
+/// once. Metadata is only cached up to ORIG_CACHE_LIMIT, but hashes are always
+/// cached, since they are small and reading a file again is what costs. The
+/// last pass takes entries out instead of copying them, so the cache is empty
+/// again when the run ends.
+#[derive(Default)]
+pub struct OrigCache {
+    meta: Mutex<BoundedMap<(PathBuf, bool), Meta>>,
+    hashes: Mutex<HashMap<(PathBuf, FileVersion), blake3::Hash>>,
+    last_pass: AtomicBool,
+}
+
+impl OrigCache {
+    /// Called before the last pass: nothing it computes is needed again.
+    pub fn start_last_pass(&self) {
+        self.last_pass.store(true, Ordering::Relaxed);
+    }
+
+    fn is_last_pass(&self) -> bool {
+        self.last_pass.load(Ordering::Relaxed)
+    }
+
+    fn get_meta(&self, key: &(PathBuf, bool)) -> Option<Meta> {
+        let mut meta = self.meta.lock().unwrap();
+        if self.is_last_pass() {
+            meta.remove(key)
+        } else {
+            meta.get(key)
+        }
+    }
+
+    fn insert_meta(&self, key: (PathBuf, bool), value: Meta, weight: usize) {
+        if !self.is_last_pass() {
+            self.meta.lock().unwrap().insert(key, value, weight);
+        }
+    }
+
+    fn get_hash(&self, path: &Path, meta: &fs::Metadata) -> Option<blake3::Hash> {
+        let key = (path.to_path_buf(), FileVersion::of(meta));
+        let mut hashes = self.hashes.lock().unwrap();
+        if self.is_last_pass() {
+            hashes.remove(&key)
+        } else {
+            hashes.get(&key).copied()
+        }
+    }
+
+    fn insert_hash(&self, path: &Path, meta: &fs::Metadata, hash: blake3::Hash) {
+        if !self.is_last_pass() {
+            let key = (path.to_path_buf(), FileVersion::of(meta));
+            self.hashes.lock().unwrap().insert(key, hash);
+        }
+    }
+
+    /// Forget the hash of a version of a file that changed while it was
+    /// hashed, since it may match neither version.
+    fn remove_hash(&self, path: &Path, meta: &fs::Metadata) {
+        let key = (path.to_path_buf(), FileVersion::of(meta));
+        self.hashes.lock().unwrap().remove(&key);
+    }
+}
+
+/// A HashMap that stops accepting entries once the total weight of its
+/// entries reaches ORIG_CACHE_LIMIT.
+struct BoundedMap<K, V> {
+    entries: HashMap<K, (V, usize)>,
+    weight: usize,
+}
+
+impl<K, V> Default for BoundedMap<K, V> {
+    fn default() -> Self {
+        BoundedMap { entries: HashMap::new(), weight: 0 }
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> BoundedMap<K, V> {
+    fn get(&self, key: &K) -> Option<V> {
+        self.entries.get(key).map(|(value, _)| value.clone())
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, weight) = self.entries.remove(key)?;
+        self.weight -= weight;
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V, weight: usize) {
+        if self.weight + weight > ORIG_CACHE_LIMIT {
+            return;
+        }
+        if let Some((_, old)) = self.entries.insert(key, (value, weight)) {
+            self.weight -= old;
+        }
+        self.weight += weight;
+    }
+}
+
+/// load_meta() for a path in the original tree, going through the cache when
+/// there is one.
+fn load_orig_meta(path: &Path, follow: bool, config: &Config) -> Meta {
+    let Some(cache) = &config.orig_cache else {
+        return load_meta(path, follow);
+    };
+
+    let key = (path.to_path_buf(), follow);
+    if let Some(meta) = cache.get_meta(&key) {
+        return meta;
+    }
+    let meta = load_meta(path, follow);
+    let weight = match &meta {
+        Meta::Dir(_, names) => 1 + names.len(),
+        _ => 1,
+    };
+    cache.insert_meta(key, meta.clone(), weight);
+    meta
+}
+
 // -- Entry point --------------------------------------------------------------
 
 /// Compare directories according to the provided Config.
@@ -169,6 +349,22 @@ pub fn compare_dirs(config: &Config, stats: &Stats) {
     compare(&config.original, &config.backup, false, config, stats);
 }
 
+/// Stop the walk if any of the --max-errors, --max-missing-percent or
+/// --fail-fast thresholds have been exceeded by the counts so far.
+///
+/// Like the Ctrl-C handler, this prints the partial summary and exits
+/// immediately, with an exit code saying the verification is incomplete.
+fn check_thresholds(config: &Config, stats: &Stats) {
+    if let Some(reason) =
+        stats.exceeded_threshold(config.max_errors, config.max_missing_percent, config.fail_fast, &config.policy)
+    {
+        println!("ABORTED: {}", reason);
+        stats.print_summary();
+        println!("WARNING: EXITING BEFORE VERIFICATION WAS COMPLETE!");
+        process::exit(crate::EXIT_INCOMPLETE);
+    }
+}
+
 // -- Comparison ---------------------------------------------------------------
 
 /// Compare two paths at the same relative position in both trees.
@@ -185,6 +381,8 @@ fn compare(
     config: &Config,
     stats: &Stats,
 ) {
+    check_thresholds(config, stats);
+
     // Check ignore first so we don't encounter errors, different fs, or special files that the user ignored.
     if config.ignore.iter().any(|ig| ig == orig || ig == backup) {
         // One side may not exist, but be conservative and tell the user we're skipping both sides.
@@ -195,8 +393,12 @@ fn compare(
         return;
     }
 
-    let meta_orig = load_meta(orig, follow);
-    let meta_back = load_meta(backup, follow);
+    let mut meta_orig = load_orig_meta(orig, follow, config);
+    let mut meta_back = load_meta(backup, follow);
+    if follow {
+        meta_orig = into_cycle_if_ancestor(meta_orig, orig, &config.original);
+        meta_back = into_cycle_if_ancestor(meta_back, backup, &config.backup);
+    }
 
     // Check one-filesystem before any processing
     #[cfg(unix)]
@@ -216,8 +418,8 @@ fn compare(
                         return;
                     }
                 }
-                // No check for Dangling (already checked with follow=false one level up the stack) or Error
-                Meta::Dangling | Meta::Error(_) => {}
+                // No check for Dangling or Cycle (already checked with follow=false one level up the stack) or Error
+                Meta::Dangling | Meta::Cycle | Meta::Error(_) | Meta::Vanished => {}
             }
         }
 
@@ -235,7 +437,7 @@ fn compare(
                         return;
                     }
                 }
-                Meta::Dangling | Meta::Error(_) => {}
+                Meta::Dangling | Meta::Cycle | Meta::Error(_) | Meta::Vanished => {}
             }
         }
     }
@@ -250,7 +452,15 @@ fn compare(
             }
             Meta::Dangling => {
                 println!("DANGLING-SYMLINK: [{}]", orig.display());
-                stats.inc_errors();
+                stats.inc_dangling();
+            }
+            Meta::Cycle => {
+                println!("SYMLINK-CYCLE: [{}] (points to an ancestor directory)", orig.display());
+                stats.inc_symlink_cycles();
+            }
+            Meta::Vanished => {
+                println!("VANISHED: [{}] (deleted since its directory was listed)", orig.display());
+                stats.inc_vanished();
             }
             _ => unreachable!(),
         }
@@ -265,7 +475,15 @@ fn compare(
             }
             Meta::Dangling => {
                 println!("DANGLING-SYMLINK: [{}]", backup.display());
-                stats.inc_errors();
+                stats.inc_dangling();
+            }
+            Meta::Cycle => {
+                println!("SYMLINK-CYCLE: [{}] (points to an ancestor directory)", backup.display());
+                stats.inc_symlink_cycles();
+            }
+            Meta::Vanished => {
+                println!("VANISHED: [{}] (deleted since its directory was listed)", backup.display());
+                stats.inc_vanished();
             }
             _ => unreachable!(),
         }
@@ -319,10 +537,10 @@ fn compare(
     // We still need to report type mismatches for the other cases.
 
     match (&meta_orig, &meta_back) {
-        // If any side is Error/Dangling/Special, that side has already been reported above.
+        // If any side is Error/Dangling/Cycle/Special, that side has already been reported above.
         // Other side will be reported below.
-        (_, Meta::Error(_) | Meta::Dangling | Meta::Special(_)) => {},
-        (Meta::Error(_) | Meta::Dangling | Meta::Special(_), _) => {},
+        (_, Meta::Error(_) | Meta::Dangling | Meta::Cycle | Meta::Vanished | Meta::Special(_)) => {},
+        (Meta::Error(_) | Meta::Dangling | Meta::Cycle | Meta::Vanished | Meta::Special(_), _) => {},
         // Symlink vs (File | Dir)
         // (File | Dir) vs Symlink
         (Meta::Symlink(_), Meta::File(_) | Meta::Dir(_, _)) |
@@ -360,11 +578,17 @@ fn compare(
     if meta_back.is_file_dir_or_symlink() {
         match meta_orig {
             // Error means we can't verify, don't suggest deletion of potentially
-            // valid backup by calling it "EXTRA".
-            Meta::Error(_) => {
+            // valid backup by calling it "EXTRA". An original that vanished
+            // was there when its directory was listed, so the same applies.
+            Meta::Error(_) | Meta::Vanished => {
                 // Let the user know we are skipping the other side, though.
                 println!("SKIP: [{}]", backup.display());
             },
+            // With --superset, whatever the backup has in place of the
+            // original is expected, so count it but don't walk it.
+            _ if config.superset => {
+                stats.inc_backup_items();
+            }
             _ => {
                 // TODO: See #24, reporting backup files as "extra" when the
                 // backup has real files but the original contains some other
@@ -397,19 +621,144 @@ fn compare_files(
         );
     }
 
-    match compare_file_content(orig, backup, orig_meta, backup_meta, config, stats) {
+    // Hardlinked copies (`cp -al`) and bind mounts give the backup the very
+    // same file as the original, so comparing their contents proves nothing.
+    // Reflinks share data blocks but not inodes, so they can't be spotted here.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if (orig_meta.dev(), orig_meta.ino()) == (backup_meta.dev(), backup_meta.ino()) {
+            stats.inc_original_items();
+            stats.inc_backup_items();
+            println!(
+                "NOT-INDEPENDENT: [{}] (same file as [{}])",
+                orig.display(),
+                backup.display()
+            );
+            stats.inc_not_independent();
+            return;
+        }
+    }
+
+    // Files of a live system may change while they're read, which looks just
+    // like corruption. Check that neither changed, and retry if they did.
+    let (mut orig_meta, mut backup_meta) = (orig_meta.clone(), backup_meta.clone());
+    let mut retries = 0;
+    let result = loop {
+        let result = compare_file_content(orig, backup, &orig_meta, &backup_meta, config, stats);
+        if matches!(
+            result,
+            FileCompareResult::OrigError | FileCompareResult::BackupError | FileCompareResult::BothError
+        ) {
+            break result;
+        }
+
+        // Comparing sizes reads nothing, so there's nothing a change could
+        // have disturbed.
+        if !reads_content(orig, &orig_meta, &backup_meta, config) {
+            break result;
+        }
+
+        let orig_now = restat(orig, &orig_meta);
+        let backup_now = restat(backup, &backup_meta);
+        match (&orig_now, &backup_now) {
+            (Restat::Unchanged, Restat::Unchanged) => break result,
+            (Restat::Vanished, _) | (_, Restat::Vanished) => {
+                report_vanished_file(orig, backup, &orig_now, &backup_now, config, stats);
+                return;
+            }
+            _ => {}
+        }
+
+        // A side that can't be stat'ed can't be compared again.
+        let retry = !matches!(orig_now, Restat::Error)
+            && !matches!(backup_now, Restat::Error)
+            && retries < config.retry_changed;
+        if !retry {
+            let side = match (&orig_now, &backup_now) {
+                (Restat::Unchanged, _) => "backup changed",
+                (_, Restat::Unchanged) => "original changed",
+                _ => "both changed",
+            };
+            stats.inc_original_items();
+            stats.inc_backup_items();
+            println!("CHANGED-DURING-VERIFY: [{}] ({})", orig.display(), side);
+            stats.inc_changed_during_verify();
+            return;
+        }
+
+        retries += 1;
+        if config.verbosity >= Verbosity::Files {
+            println!("DEBUG: Changed during verify, retry {} of [{}]", retries, orig.display());
+        }
+        if let Restat::Changed(m) = orig_now {
+            if let Some(cache) = &config.orig_cache {
+                cache.remove_hash(orig, &orig_meta);
+            }
+            orig_meta = m;
+        }
+        if let Restat::Changed(m) = backup_now {
+            backup_meta = m;
+        }
+    };
+    let (orig_meta, backup_meta) = (&orig_meta, &backup_meta);
+
+    match result {
         FileCompareResult::Different(r) => {
             stats.inc_original_items();
             stats.inc_backup_items();
 
-            println!("DIFFERENT-FILE [{}]: [{}]", r, orig.display());
+            let sizes = if r.size {
+                format!(" (original {} bytes, backup {} bytes)", orig_meta.len(), backup_meta.len())
+            } else {
+                String::new()
+            };
+            println!(
+                "DIFFERENT-FILE [{}]: [{}]{}{}",
+                r,
+                orig.display(),
+                previous_note(orig, config, stats),
+                sizes
+            );
             stats.inc_different();
+
+            if config.locate_diff && (r.sample || r.hash) {
+                print_diff_location(orig, backup, config, stats);
+            }
+            if config.chunk_diff && (r.sample || r.hash) {
+                print_diff_chunks(orig, backup, orig_meta, config, stats);
+            }
+            if config.check_zeroed {
+                print_zeroed(orig, backup, orig_meta, config, stats);
+            }
         }
         FileCompareResult::Same => {
             stats.inc_original_items();
             stats.inc_backup_items();
 
+            // With --all the contents are known to match, otherwise zeroed
+            // blocks may have gone unnoticed.
+            let zeroed = config.check_zeroed
+                && !config.all
+                && print_zeroed(orig, backup, orig_meta, config, stats);
+            if zeroed {
+                stats.inc_different();
+            } else {
+                stats.inc_similarities();
+            }
+        }
+        FileCompareResult::AllowedGrowth => {
+            stats.inc_original_items();
+            stats.inc_backup_items();
+
+            println!(
+                "ALLOWED-GROWTH: [{}] (original {} bytes, backup {} bytes)",
+                orig.display(),
+                orig_meta.len(),
+                backup_meta.len()
+            );
             stats.inc_similarities();
+            stats.inc_allowed_growth();
         }
         FileCompareResult::OrigError => {
             stats.inc_original_items();
@@ -435,6 +784,100 @@ fn compare_files(
     }
 }
 
+/// What stat'ing a file again after comparing it found.
+enum Restat {
+    Unchanged,
+    Changed(fs::Metadata),
+    /// Deleted since it was stat'ed.
+    Vanished,
+    /// Can't be stat'ed for another reason.
+    Error,
+}
+
+/// Stat `path` again and compare it with `before`.
+fn restat(path: &Path, before: &fs::Metadata) -> Restat {
+    let now = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Restat::Vanished,
+        Err(_) => return Restat::Error,
+    };
+    if FileVersion::of(&now) == FileVersion::of(before) {
+        Restat::Unchanged
+    } else {
+        Restat::Changed(now)
+    }
+}
+
+/// What tells one version of a file from another: writing to a file or
+/// replacing it changes at least one of these.
+#[derive(PartialEq, Eq, Hash)]
+struct FileVersion {
+    size: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    changed: (i64, i64),
+    #[cfg(unix)]
+    id: (u64, u64),
+}
+
+impl FileVersion {
+    fn of(meta: &fs::Metadata) -> FileVersion {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+        FileVersion {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            #[cfg(unix)]
+            changed: (meta.ctime(), meta.ctime_nsec()),
+            #[cfg(unix)]
+            id: (meta.dev(), meta.ino()),
+        }
+    }
+}
+
+/// Whether compare_file_content() reads either file instead of only
+/// comparing their sizes.
+fn reads_content(orig: &Path, orig_meta: &fs::Metadata, backup_meta: &fs::Metadata, config: &Config) -> bool {
+    let (orig_size, backup_size) = (orig_meta.len(), backup_meta.len());
+    if orig_size != backup_size {
+        config.check_prefix || (orig_size > backup_size && growth_allowed(orig, config))
+    } else {
+        config.all || (config.samples > 0 && orig_size > 0)
+    }
+}
+
+/// Report files deleted while they were compared the same way compare()
+/// reports entries deleted before they were stat'ed: VANISHED, and the other
+/// side skipped (if the original is gone) or missing (if only the backup is).
+fn report_vanished_file(
+    orig: &Path,
+    backup: &Path,
+    orig_now: &Restat,
+    backup_now: &Restat,
+    config: &Config,
+    stats: &Stats,
+) {
+    let orig_gone = matches!(orig_now, Restat::Vanished);
+    let backup_gone = matches!(backup_now, Restat::Vanished);
+
+    if orig_gone {
+        stats.inc_original_items();
+        println!("VANISHED: [{}] (deleted while it was compared)", orig.display());
+        stats.inc_vanished();
+    }
+    if backup_gone {
+        stats.inc_backup_items();
+        println!("VANISHED: [{}] (deleted while it was compared)", backup.display());
+        stats.inc_vanished();
+    }
+
+    if !orig_gone {
+        report(orig, Direction::Missing, false, true, config, stats);
+    } else if !backup_gone {
+        println!("SKIP: [{}]", backup.display());
+    }
+}
+
 /// Compare two directories.
 ///
 /// Pre: Both are dirs. Entries pre-loaded. Neither counted.
@@ -466,28 +909,138 @@ fn compare_directories(
     // Both directories being present counts as a similarity, even if their contents differ
     stats.inc_similarities();
 
-    let mut backup_set: HashSet<&OsString> = backup_entries.into_iter().collect();
+    let mut backup_set: HashSet<&OsString> = backup_entries.iter().collect();
 
     for name in orig_entries {
         let orig_path = orig.join(name);
+
+        // A --map rule for this entry pairs it with its mapped path instead of
+        // the same name, which is then left for the extras below.
+        if compare_mapped(&orig_path, config, stats) {
+            continue;
+        }
+
         let backup_path = backup.join(name);
 
         let in_backup = backup_set.remove(name);
         if in_backup {
             compare(&orig_path, &backup_path, false, config, stats);
-        } else {
+        } else if !walk_map_ancestor(&orig_path, Direction::Missing, config, stats) {
             report(&orig_path, Direction::Missing, false, true, config, stats);
         }
     }
 
-    // Remaining in backup_set are extras
-    let mut extras: Vec<&OsString> = backup_set.into_iter().collect();
+    // Remaining in backup_set are extras. With --superset they are expected,
+    // so we don't even descend into them.
+    if config.superset {
+        return;
+    }
+
+    let mut extras: Vec<&OsString> = backup_set
+        .into_iter()
+        .filter(|name| !is_mapped_from_original(&backup.join(name), config))
+        .collect();
     extras.sort();
 
     for name in &extras {
         let backup_path = backup.join(name);
-        report(&backup_path, Direction::Extra, false, true, config, stats);
+        if !walk_map_ancestor(&backup_path, Direction::Extra, config, stats) {
+            report(&backup_path, Direction::Extra, false, true, config, stats);
+        }
+    }
+}
+
+/// If a --map rule applies to `orig`, compare it against its mapped path, or
+/// report it as missing if that doesn't exist. Returns whether a rule applied.
+fn compare_mapped(orig: &Path, config: &Config, stats: &Stats) -> bool {
+    let Some(mapped) = mapped_backup_path(orig, config) else {
+        return false;
+    };
+    if fs::symlink_metadata(&mapped).is_ok() {
+        compare(orig, &mapped, false, config, stats);
+    } else {
+        report(orig, Direction::Missing, false, true, config, stats);
     }
+    true
+}
+
+/// Walk a directory that only one side has because it leads to one side of a
+/// --map rule, e.g. home/alice in the original for
+/// --map home/alice/docs=users/alice/documents, or users/alice in the backup.
+/// The directory itself is expected to be absent from the other side, so only
+/// the entries that aren't on the way to a mapped path are missing or extra.
+///
+/// Returns false without doing anything if `path` isn't such a directory, so
+/// the caller reports it as usual.
+fn walk_map_ancestor(path: &Path, direction: Direction, config: &Config, stats: &Stats) -> bool {
+    if !is_map_ancestor(path, direction, config) || config.ignore.iter().any(|ig| ig == path) {
+        return false;
+    }
+    let meta = match direction {
+        Direction::Missing => load_orig_meta(path, false, config),
+        Direction::Extra => load_meta(path, false),
+    };
+    let Meta::Dir(_, entries) = meta else {
+        return false;
+    };
+
+    if config.verbosity >= Verbosity::Dirs {
+        println!("DEBUG: Walking [{}] for --map", path.display());
+    }
+    direction.inc_items(stats);
+
+    for name in &entries {
+        let child = path.join(name);
+        let handled = match direction {
+            Direction::Missing => compare_mapped(&child, config, stats),
+            Direction::Extra => is_mapped_from_original(&child, config),
+        };
+        if !handled && !walk_map_ancestor(&child, direction, config, stats) {
+            report(&child, direction, false, true, config, stats);
+        }
+    }
+    true
+}
+
+/// Whether `path` is a directory above the original (Missing) or backup
+/// (Extra) side of a --map rule.
+fn is_map_ancestor(path: &Path, direction: Direction, config: &Config) -> bool {
+    let root = match direction {
+        Direction::Missing => &config.original,
+        Direction::Extra => &config.backup,
+    };
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    config.map.iter().any(|m| {
+        let side = match direction {
+            Direction::Missing => &m.orig,
+            Direction::Extra => &m.backup,
+        };
+        side != relative && side.starts_with(relative)
+    })
+}
+
+/// Backup path of `orig` according to a --map rule, if one applies to it.
+fn mapped_backup_path(orig: &Path, config: &Config) -> Option<PathBuf> {
+    let relative = orig.strip_prefix(&config.original).ok()?;
+    config
+        .map
+        .iter()
+        .find(|m| m.orig == relative)
+        .map(|m| config.backup.join(&m.backup))
+}
+
+/// Whether `backup` is the target of a --map rule whose original exists, so
+/// it was (or will be) compared against that instead of being an extra.
+fn is_mapped_from_original(backup: &Path, config: &Config) -> bool {
+    let Ok(relative) = backup.strip_prefix(&config.backup) else {
+        return false;
+    };
+    config
+        .map
+        .iter()
+        .any(|m| m.backup == relative && fs::symlink_metadata(config.original.join(&m.orig)).is_ok())
 }
 
 /// Compare two symlinks.
@@ -546,24 +1099,71 @@ fn compare_symlinks(
     stats.inc_backup_items();
 
     let targets_differ = orig_target != backup_target;
-    if targets_differ {
-        println!(
-            "DIFFERENT-SYMLINK-TARGET: [{}] (targets differ: {:?} vs {:?})",
-            orig.display(),
-            orig_target,
-            backup_target
-        );
-        stats.inc_different();
-    } else {
-        stats.inc_similarities();
+    let normalized = (targets_differ && config.normalize_symlinks).then(|| {
+        (
+            normalized_target(orig, &orig_target, &config.original, config),
+            normalized_target(backup, &backup_target, &config.backup, config),
+        )
+    });
+
+    match &normalized {
+        Some((orig_normalized, backup_normalized)) if orig_normalized != backup_normalized => {
+            println!(
+                "DIFFERENT-SYMLINK-TARGET: [{}] (targets differ: {:?} vs {:?}, normalized: {:?} vs {:?}){}",
+                orig.display(),
+                orig_target,
+                backup_target,
+                orig_normalized,
+                backup_normalized,
+                previous_note(orig, config, stats)
+            );
+            stats.inc_different();
+        }
+        None if targets_differ => {
+            println!(
+                "DIFFERENT-SYMLINK-TARGET: [{}] (targets differ: {:?} vs {:?}){}",
+                orig.display(),
+                orig_target,
+                backup_target,
+                previous_note(orig, config, stats)
+            );
+            stats.inc_different();
+        }
+        _ => {
+            stats.inc_similarities();
+        }
     }
 
     if config.follow {
+        let refusal = follow_refusal(orig, &config.original, "original", config)
+            .or_else(|| follow_refusal(backup, &config.backup, "backup", config));
+        if let Some(reason) = refusal {
+            println!("SYMLINK-SKIPPED: [{}] (not followed: {})", orig.display(), reason);
+            stats.inc_skipped();
+            return;
+        }
+
+        check_backup_symlink(backup, Some(orig), config, stats);
+
         // --follow: compare resolved content as additional items.
         // Symlinks are already counted above. The resolved content is
         // counted separately by compare (via its helpers or report).
         compare(orig, backup, true, config, stats);
     } else {
+        if config.check_dangling {
+            let orig_dangles = is_dangling(orig);
+            if orig_dangles {
+                println!("DANGLING-SYMLINK: [{}]", orig.display());
+                stats.inc_dangling();
+            }
+            if is_dangling(backup) {
+                // Worse than a dangling original: the backup lost the target.
+                let note = if orig_dangles { "" } else { " (original's target exists)" };
+                println!("DANGLING-SYMLINK: [{}]{}", backup.display(), note);
+                stats.inc_dangling();
+            }
+        }
+
         println!(
             "SYMLINK-SKIPPED: [{}] (use --follow to compare resolved content)",
             orig.display()
@@ -572,37 +1172,149 @@ fn compare_symlinks(
     }
 }
 
-// -- report -------------------------------------------------------------------
-
-/// Report a path and all descendants as missing or extra.
+/// With --follow, why the symlink `link` under `root` shouldn't be followed
+/// according to --follow-within-root, --follow-files-only and
+/// --max-follow-depth, if at all. `side` names the root in the reason.
 ///
-/// Pre: Entry has NOT been counted as an item. Callers other than report() itself must set follow=false.
-/// Post: Entry counted. Classified. Descendants processed. Logs output.
-fn report(
-    path: &Path,
-    direction: Direction,
-    follow: bool,
-    // Gets set to true for the top-level call, and false for recursive calls unless verbosity level is >= Files.
-    print: bool,
-    config: &Config,
-    stats: &Stats,
-) {
-    // Check ignore first (before any I/O)
-    // TODO (#35): --ignore paths should automatically apply to the other side
-    // (i.e. to the backup, when ignoring a folder in original), but this is not
-    // implemented yet.
-    if config.ignore.iter().any(|ig| ig == path) {
-        println!("SKIP: [{}]", path.display());
-        stats.inc_skipped();
-        return;
+/// Symlinks that can't be resolved are followed, so that compare()/report()
+/// report them as dangling or errors like they would without these options.
+fn follow_refusal(link: &Path, root: &Path, side: &str, config: &Config) -> Option<String> {
+    if config.follow_within_root {
+        if let Ok(resolved) = fs::canonicalize(link) {
+            if !resolved.starts_with(root) {
+                return Some(format!("target is outside the {}", side));
+            }
+        }
     }
 
-    let meta = load_meta(path, follow);
+    if config.follow_files_only && fs::metadata(link).is_ok_and(|m| m.is_dir()) {
+        return Some("target is a directory".to_string());
+    }
 
-    // Check --one-filesystem before processing entries on different filesystems.
-    // This handles both mount points (follow=false) and resolved symlinks (follow=true).
-    #[cfg(unix)]
-    {
+    if let Some(max) = config.max_follow_depth {
+        // Every symlink on the way down from the root was followed to get here.
+        let followed = link
+            .ancestors()
+            .skip(1)
+            .take_while(|a| *a != root)
+            .filter(|a| fs::symlink_metadata(a).is_ok_and(|m| m.file_type().is_symlink()))
+            .count();
+        if followed >= max {
+            return Some(format!("--max-follow-depth {} reached", max));
+        }
+    }
+
+    None
+}
+
+/// Whether the symlink `path` points to something that doesn't exist, for
+/// --check-dangling. Other errors (e.g. ELOOP) aren't dangling.
+fn is_dangling(path: &Path) -> bool {
+    matches!(fs::metadata(path), Err(e) if e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Where the symlink `link` with `target` points, for --normalize-symlinks.
+///
+/// Relative targets are resolved lexically from the symlink's directory, so
+/// `../lib/x` and `./../lib/x` are the same. Targets inside `root` (or, for
+/// absolute targets copied verbatim, inside the original) are returned
+/// relative to it, so they're comparable between the trees. Anything else is
+/// returned as the absolute path it points to.
+fn normalized_target(link: &Path, target: &Path, root: &Path, config: &Config) -> PathBuf {
+    let absolute = match link.parent() {
+        Some(dir) if target.is_relative() => normalize_path(&dir.join(target)),
+        _ => normalize_path(target),
+    };
+
+    for base in [root, config.original.as_path()] {
+        if let Ok(relative) = absolute.strip_prefix(base) {
+            return relative.to_path_buf();
+        }
+    }
+    absolute
+}
+
+/// With --follow, check where a followed backup symlink resolves to. Outside the
+/// backup, the backup depends on something it doesn't contain. Inside the
+/// original (e.g. a "backup" made with `cp -s`), following it compares the
+/// original with itself, so that always counts as an error.
+///
+/// A symlink outside the backup isn't reported if the original's symlink
+/// (`orig`, when there is one) resolves to the same place, e.g. both point
+/// to /dev/null. That's a faithful copy of a symlink that also leaves the
+/// original.
+///
+/// Dangling symlinks are left for compare()/report() to report.
+fn check_backup_symlink(backup: &Path, orig: Option<&Path>, config: &Config, stats: &Stats) {
+    let Ok(resolved) = fs::canonicalize(backup) else {
+        return;
+    };
+
+    let in_backup = resolved.starts_with(&config.backup);
+    let in_original = resolved.starts_with(&config.original);
+    // When one root is inside the other, the more specific one wins.
+    if in_original && (!in_backup || config.original.starts_with(&config.backup)) {
+        println!(
+            "SYMLINK-INTO-ORIGINAL: [{}] -> [{}] (compared with itself!)",
+            backup.display(),
+            resolved.display()
+        );
+        stats.inc_symlinks_into_original();
+    } else if !in_backup && orig.and_then(|o| fs::canonicalize(o).ok()) != Some(resolved.clone()) {
+        println!(
+            "SYMLINK-OUTSIDE-BACKUP: [{}] -> [{}]",
+            backup.display(),
+            resolved.display()
+        );
+        stats.inc_symlinks_outside_backup();
+    }
+}
+
+// -- report -------------------------------------------------------------------
+
+/// Report a path and all descendants as missing or extra.
+///
+/// Pre: Entry has NOT been counted as an item. Callers other than report() itself must set follow=false.
+/// Post: Entry counted. Classified. Descendants processed. Logs output.
+fn report(
+    path: &Path,
+    direction: Direction,
+    follow: bool,
+    // Gets set to true for the top-level call, and false for recursive calls unless verbosity level is >= Files.
+    print: bool,
+    config: &Config,
+    stats: &Stats,
+) {
+    check_thresholds(config, stats);
+
+    // Check ignore first (before any I/O)
+    // TODO (#35): --ignore paths should automatically apply to the other side
+    // (i.e. to the backup, when ignoring a folder in original), but this is not
+    // implemented yet.
+    if config.ignore.iter().any(|ig| ig == path) {
+        println!("SKIP: [{}]", path.display());
+        stats.inc_skipped();
+        return;
+    }
+
+    let meta = match direction {
+        Direction::Missing => load_orig_meta(path, follow, config),
+        Direction::Extra => load_meta(path, follow),
+    };
+    let meta = if follow {
+        let root = match direction {
+            Direction::Missing => &config.original,
+            Direction::Extra => &config.backup,
+        };
+        into_cycle_if_ancestor(meta, path, root)
+    } else {
+        meta
+    };
+
+    // Check --one-filesystem before processing entries on different filesystems.
+    // This handles both mount points (follow=false) and resolved symlinks (follow=true).
+    #[cfg(unix)]
+    {
         use std::os::unix::fs::MetadataExt;
         let root_dev = match direction {
             Direction::Missing => config.original_device,
@@ -615,10 +1327,10 @@ fn report(
                 Meta::Dir(m, _) | Meta::File(m) | Meta::Symlink(m) | Meta::Special(m) => {
                     Some(m.dev())
                 }
-                // We only see dangling with follow=true, and only report()
-                // calls report() with follow=true, meaning the report() one
-                // stack level up already did the FS check.
-                Meta::Dangling | Meta::Error(_) => None,
+                // We only see dangling and cycles with follow=true, and only
+                // report() calls report() with follow=true, meaning the
+                // report() one stack level up already did the FS check.
+                Meta::Dangling | Meta::Cycle | Meta::Error(_) | Meta::Vanished => None,
             };
 
             if let Some(dev) = entry_dev {
@@ -633,7 +1345,12 @@ fn report(
                     if !follow {
                         direction.inc_items(stats);
                         if print {
-                            println!("{}: [{}]", direction.prefix(&meta), path.display());
+                            println!(
+                                "{}: [{}]{}",
+                                direction.prefix(&meta),
+                                path.display(),
+                                direction.previous_note(path, config, stats)
+                            );
                         }
                         direction.inc_missing_or_extra_count(stats);
                     }
@@ -654,7 +1371,15 @@ fn report(
         }
         Meta::Dangling => {
             println!("DANGLING-SYMLINK: [{}]", path.display());
-            stats.inc_errors();
+            stats.inc_dangling();
+        }
+        Meta::Cycle => {
+            println!("SYMLINK-CYCLE: [{}] (points to an ancestor directory)", path.display());
+            stats.inc_symlink_cycles();
+        }
+        Meta::Vanished => {
+            println!("VANISHED: [{}] (deleted since its directory was listed)", path.display());
+            stats.inc_vanished();
         }
         Meta::Special(_) => {
             println!("SPECIAL-FILE: [{}]", path.display());
@@ -663,13 +1388,19 @@ fn report(
         _ => {}
     }
 
-    // If we have a dangling symlink, this means follow=true, and it's
-    // already been reported as MISSING-SYMLINK by the report() one stack
+    // If we have a dangling symlink or a cycle, this means follow=true, and
+    // it's already been reported as MISSING-SYMLINK by the report() one stack
     // level up. Only recursive calls to report() set follow=true.
+    // A vanished entry no longer exists, so it isn't missing or extra either.
     // Everything else gets reported as MISSING/EXTRA here.
-    if !matches!(meta, Meta::Dangling) {
+    if !matches!(meta, Meta::Dangling | Meta::Cycle | Meta::Vanished) {
         if print {
-            println!("{}: [{}]", direction.prefix(&meta), path.display());
+            println!(
+                "{}: [{}]{}",
+                direction.prefix(&meta),
+                path.display(),
+                direction.previous_note(path, config, stats)
+            );
         }
         direction.inc_missing_or_extra_count(stats);
     }
@@ -686,17 +1417,153 @@ fn report(
             }
         }
         Meta::Symlink(_) if config.follow => {
+            let refusal = match direction {
+                Direction::Missing => follow_refusal(path, &config.original, "original", config),
+                Direction::Extra => follow_refusal(path, &config.backup, "backup", config),
+            };
+            if let Some(reason) = refusal {
+                if print {
+                    println!("SYMLINK-SKIPPED: [{}] (not followed: {})", path.display(), reason);
+                }
+                stats.inc_skipped();
+                return;
+            }
+            if matches!(direction, Direction::Extra) {
+                check_backup_symlink(path, None, config, stats);
+            }
             report(path, direction, true, print_children, config, stats);
         }
+        Meta::Symlink(_) if config.check_dangling && is_dangling(path) => {
+            if print {
+                println!("DANGLING-SYMLINK: [{}]", path.display());
+            }
+            stats.inc_dangling();
+        }
         // All of these leaf types have already been reported above.
-        Meta::File(_) | Meta::Symlink(_) | Meta::Special(_) | Meta::Dangling | Meta::Error(_) => {}
+        Meta::File(_)
+        | Meta::Symlink(_)
+        | Meta::Special(_)
+        | Meta::Dangling
+        | Meta::Cycle
+        | Meta::Error(_)
+        | Meta::Vanished => {}
     }
 }
 
+// -- Three-way classification (--previous) -----------------------------------
+
+/// How an entry that differs between the original and the backup relates to
+/// the previous backup.
+#[derive(Clone, Copy)]
+enum Change {
+    /// The original no longer matches the previous backup, so the difference
+    /// is expected.
+    ChangedAtSource,
+    /// The original still matches the previous backup, so the current backup
+    /// should have matched too.
+    CorruptedInBackup,
+    /// The previous backup doesn't have the entry at all.
+    NewSinceLastBackup,
+}
+
+/// With --previous, classify a DIFFERENT or MISSING original entry against
+/// the previous backup and return a note for the end of its output line.
+/// Returns "" without --previous, or when the previous backup can't be read.
+fn previous_note(orig: &Path, config: &Config, stats: &Stats) -> &'static str {
+    let Some(previous) = &config.previous else {
+        return "";
+    };
+
+    let change = match classify_change(orig, previous, config) {
+        Some(change) => change,
+        None => return "",
+    };
+
+    match change {
+        Change::ChangedAtSource => {
+            stats.inc_changed_at_source();
+            " (changed at source)"
+        }
+        Change::CorruptedInBackup => {
+            stats.inc_corrupted_in_backup();
+            " (corrupted in backup)"
+        }
+        Change::NewSinceLastBackup => {
+            stats.inc_new_since_last_backup();
+            " (new since last backup)"
+        }
+    }
+}
+
+/// Compare an original entry with the same relative path in the previous
+/// backup. Files are compared the same way as against the current backup
+/// (size, then --samples, then --all); symlinks by target; anything else by
+/// type only.
+fn classify_change(orig: &Path, previous: &Path, config: &Config) -> Option<Change> {
+    let rel = orig.strip_prefix(&config.original).ok()?;
+    // Joining an empty path would add a trailing slash, which fails for file roots.
+    let prev = if rel.as_os_str().is_empty() { previous.to_path_buf() } else { previous.join(rel) };
+
+    let prev_meta = match fs::symlink_metadata(&prev) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Some(Change::NewSinceLastBackup);
+        }
+        Err(_) => return None,
+    };
+    let orig_meta = fs::symlink_metadata(orig).ok()?;
+
+    let (oft, pft) = (orig_meta.file_type(), prev_meta.file_type());
+    let unchanged = if oft.is_file() && pft.is_file() {
+        files_match(orig, &prev, &orig_meta, &prev_meta, config)?
+    } else if oft.is_symlink() && pft.is_symlink() {
+        fs::read_link(orig).ok()? == fs::read_link(&prev).ok()?
+    } else {
+        oft.is_dir() == pft.is_dir() && oft.is_symlink() == pft.is_symlink()
+    };
+
+    if unchanged {
+        Some(Change::CorruptedInBackup)
+    } else {
+        Some(Change::ChangedAtSource)
+    }
+}
+
+/// Quietly check whether two files match using the configured checks.
+/// Returns None if either file can't be read.
+fn files_match(
+    a: &Path,
+    b: &Path,
+    a_meta: &fs::Metadata,
+    b_meta: &fs::Metadata,
+    config: &Config,
+) -> Option<bool> {
+    let size = a_meta.len();
+    if size != b_meta.len() {
+        return Some(false);
+    }
+
+    if config.samples > 0 && size > 0 && !samples_match(a, b, &sample_offsets(size, a, config)).ok()? {
+        return Some(false);
+    }
+
+    if config.all {
+        let (a_hash, b_hash) =
+            rayon::join(|| hash_orig_file(a, a_meta, config), || hash_file(b, config));
+        if a_hash.ok()? != b_hash.ok()? {
+            return Some(false);
+        }
+    }
+
+    Some(true)
+}
+
 // -- File content comparison --------------------------------------------------
 
 enum FileCompareResult {
     Same,
+    /// The original grew since the backup, which --allow-growth allows.
+    AllowedGrowth,
     Different(DiffReasons),
     /// Original file failed to read. Error has already been reported and counted.
     OrigError,
@@ -723,54 +1590,65 @@ fn compare_file_content(
     let orig_size = orig_meta.len();
     let backup_size = backup_meta.len();
 
+    if orig_size > backup_size && growth_allowed(orig, config) {
+        match growth_prefix_matches(orig, backup, backup_size, config) {
+            Ok(true) => return FileCompareResult::AllowedGrowth,
+            Ok(false) => {}
+            // Can't tell, so it stays a plain size difference.
+            Err(e) => {
+                println!("ERROR: {}", e);
+                stats.inc_errors();
+            }
+        }
+    }
+
     if orig_size != backup_size {
         reasons.size = true;
+
+        if config.check_prefix {
+            match size_change(orig, backup, orig_meta, backup_meta) {
+                Ok(change) => reasons.size_change = Some(change),
+                Err(e) => {
+                    println!(
+                        "ERROR: Cannot compare [{}] with the start of [{}]: {}",
+                        orig.display(),
+                        backup.display(),
+                        e
+                    );
+                    stats.inc_errors();
+                }
+            }
+        }
     }
 
     // Sample check — only if sizes match and samples > 0
     if !reasons.any() && config.samples > 0 && orig_size > 0 {
-        let mut rng = rand::rng();
-        let sample_size: u64 = 32;
-
-        for _ in 0..config.samples {
-            let max_offset = orig_size.saturating_sub(sample_size);
-            let offset = rng.random_range(0..=max_offset);
-            let read_len = (orig_size - offset).min(sample_size) as usize;
-
-            match (
-                read_sample(orig, offset, read_len),
-                read_sample(backup, offset, read_len),
-            ) {
-                (Ok(a), Ok(b)) => {
-                    if a != b {
-                        reasons.sample = true;
-                        break;
-                    }
-                }
-                (Err(e), Ok(_)) => {
-                    println!("ERROR: Cannot read sample from [{}]: {}", orig.display(), e);
-                    stats.inc_errors();
-                    return FileCompareResult::OrigError;
-                }
-                (Ok(_), Err(e)) => {
-                    println!("ERROR: Cannot read sample from [{}]: {}", backup.display(), e);
-                    stats.inc_errors();
-                    return FileCompareResult::BackupError;
-                }
-                (Err(e1), Err(e2)) => {
-                    println!("ERROR: Cannot read sample from [{}]: {}", orig.display(), e1);
-                    stats.inc_errors();
-                    println!("ERROR: Cannot read sample from [{}]: {}", backup.display(), e2);
-                    stats.inc_errors();
-                    return FileCompareResult::BothError;
-                }
+        match samples_match(orig, backup, &sample_offsets(orig_size, orig, config)) {
+            Ok(matched) => reasons.sample = !matched,
+            Err(SampleError::Orig(e)) => {
+                println!("ERROR: Cannot read sample from [{}]: {}", orig.display(), e);
+                stats.inc_errors();
+                return FileCompareResult::OrigError;
+            }
+            Err(SampleError::Backup(e)) => {
+                println!("ERROR: Cannot read sample from [{}]: {}", backup.display(), e);
+                stats.inc_errors();
+                return FileCompareResult::BackupError;
+            }
+            Err(SampleError::Both(e1, e2)) => {
+                println!("ERROR: Cannot read sample from [{}]: {}", orig.display(), e1);
+                stats.inc_errors();
+                println!("ERROR: Cannot read sample from [{}]: {}", backup.display(), e2);
+                stats.inc_errors();
+                return FileCompareResult::BothError;
             }
         }
     }
 
     // BLAKE3 hash check — only if no prior mismatch
     if !reasons.any() && config.all {
-        let (orig_result, backup_result) = rayon::join(|| hash_file(orig), || hash_file(backup));
+        let (orig_result, backup_result) =
+            rayon::join(|| hash_orig_file(orig, orig_meta, config), || hash_file(backup, config));
 
         let orig_hash = match orig_result {
             Ok(h) => Some(h),
@@ -813,18 +1691,691 @@ fn compare_file_content(
     }
 }
 
+/// Whether `orig` matches one of the --allow-growth patterns.
+fn growth_allowed(orig: &Path, config: &Config) -> bool {
+    let relative = orig.strip_prefix(&config.original).unwrap_or(orig);
+    let name = orig.file_name().unwrap_or_default();
+    config.allow_growth.iter().any(|pattern| {
+        let subject = if pattern.contains('/') { relative.as_os_str() } else { name };
+        glob_matches(pattern.as_bytes(), subject.as_encoded_bytes())
+    })
+}
+
+/// Match `text` against a pattern where `*` matches any bytes except `/` and
+/// `?` matches one byte except `/`.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => {
+            let end = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=end).any(|i| glob_matches(rest, &text[i..]))
+        }
+        Some((&p, rest)) => match text.split_first() {
+            Some((&c, text_rest)) if (p == b'?' && c != b'/') || p == c => {
+                glob_matches(rest, text_rest)
+            }
+            _ => false,
+        },
+    }
+}
+
+/// Whether the first `len` bytes of `orig` match `backup` (which is `len`
+/// bytes long), using the same checks as compare_file_content() does for
+/// whole files. Without --samples or --all, the prefix is hashed anyway:
+/// equal sizes say nothing here, so the sizes alone can't allow the growth.
+fn growth_prefix_matches(
+    orig: &Path,
+    backup: &Path,
+    len: u64,
+    config: &Config,
+) -> Result<bool, String> {
+    if config.samples > 0 && len > 0 {
+        let matched = samples_match(orig, backup, &sample_offsets(len, orig, config)).map_err(|e| {
+            let (path, e) = match e {
+                SampleError::Orig(e) | SampleError::Both(e, _) => (orig, e),
+                SampleError::Backup(e) => (backup, e),
+            };
+            format!("Cannot read sample from [{}]: {}", path.display(), e)
+        })?;
+        if !matched {
+            return Ok(false);
+        }
+    }
+
+    if config.all || config.samples == 0 {
+        let (orig_hash, backup_hash) =
+            rayon::join(|| hash_prefix(orig, len), || hash_file(backup, config));
+        let orig_hash = orig_hash.map_err(|e| format!("Cannot hash [{}]: {}", orig.display(), e))?;
+        let backup_hash = backup_hash.map_err(|e| format!("Cannot hash [{}]: {}", backup.display(), e))?;
+        if orig_hash != backup_hash {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// BLAKE3 hash of the first `len` bytes of a file.
+fn hash_prefix(path: &Path, len: u64) -> std::io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut fs::File::open(path)?.take(len), &mut hasher)?;
+    Ok(hasher.finalize())
+}
+
+/// Classify two files of different sizes by whether the shorter one is the
+/// start of the longer one. This reads all of the shorter file.
+fn size_change(
+    orig: &Path,
+    backup: &Path,
+    orig_meta: &fs::Metadata,
+    backup_meta: &fs::Metadata,
+) -> std::io::Result<SizeChange> {
+    if locate_difference(orig, backup, u64::MAX)?.first.is_some() {
+        return Ok(SizeChange::Mismatch);
+    }
+    if orig_meta.len() < backup_meta.len() {
+        return Ok(SizeChange::Appended);
+    }
+    // An interrupted copy is newer than its original, while an original that
+    // grew since the backup (a log file, say) is newer than the backup.
+    match (orig_meta.modified(), backup_meta.modified()) {
+        (Ok(o), Ok(b)) if o > b => Ok(SizeChange::Grown),
+        _ => Ok(SizeChange::Truncated),
+    }
+}
+
+/// Where two files differ, as found by --locate-diff.
+struct DiffLocation {
+    /// Offset of the first differing byte.
+    first: Option<u64>,
+    /// Number of maximal runs of differing bytes.
+    ranges: u64,
+    /// Number of differing bytes.
+    bytes: u64,
+    /// Number of bytes read from each file, if --locate-limit was reached
+    /// before the end of the files.
+    stopped_at: Option<u64>,
+}
+
+/// Print a DIFF-LOCATION line for two files that were found to differ.
+fn print_diff_location(orig: &Path, backup: &Path, config: &Config, stats: &Stats) {
+    let location = match locate_difference(orig, backup, config.locate_limit) {
+        Ok(l) => l,
+        Err(e) => {
+            println!(
+                "ERROR: Cannot locate difference between [{}] and [{}]: {}",
+                orig.display(),
+                backup.display(),
+                e
+            );
+            stats.inc_errors();
+            return;
+        }
+    };
+
+    let scope = match location.stopped_at {
+        Some(n) => format!(" in the first {} bytes", n),
+        None => String::new(),
+    };
+    match location.first {
+        Some(first) => println!(
+            "DIFF-LOCATION: [{}] first difference at byte {}, {} differing ranges ({} bytes){}",
+            orig.display(),
+            first,
+            location.ranges,
+            location.bytes,
+            scope
+        ),
+        // The files changed since they were compared, or the difference is
+        // past --locate-limit.
+        None => println!("DIFF-LOCATION: [{}] no difference found{}", orig.display(), scope),
+    }
+}
+
+/// Compare two files byte by byte, reading at most `limit` bytes of each.
+/// Only the common length of the files is compared.
+fn locate_difference(orig: &Path, backup: &Path, limit: u64) -> std::io::Result<DiffLocation> {
+    const CHUNK: usize = 1 << 20;
+
+    let (mut a, mut b) = (fs::File::open(orig)?, fs::File::open(backup)?);
+    let (mut a_buf, mut b_buf) = (vec![0u8; CHUNK], vec![0u8; CHUNK]);
+    let mut location = DiffLocation { first: None, ranges: 0, bytes: 0, stopped_at: None };
+    let mut in_range = false;
+    let mut pos = 0u64;
+
+    loop {
+        if pos >= limit {
+            location.stopped_at = Some(pos);
+            break;
+        }
+        let want = CHUNK.min((limit - pos).try_into().unwrap_or(CHUNK));
+        let (a_len, b_len) = rayon::join(
+            || read_full(&mut a, &mut a_buf[..want]),
+            || read_full(&mut b, &mut b_buf[..want]),
+        );
+        let len = a_len?.min(b_len?);
+        if len == 0 {
+            break;
+        }
+
+        for (i, (x, y)) in a_buf[..len].iter().zip(&b_buf[..len]).enumerate() {
+            if x != y {
+                location.first.get_or_insert(pos + i as u64);
+                location.bytes += 1;
+                if !in_range {
+                    location.ranges += 1;
+                }
+            }
+            in_range = x != y;
+        }
+        pos += len as u64;
+        if len < want {
+            break;
+        }
+    }
+
+    Ok(location)
+}
+
+/// Read until `buf` is full or the end of the file, returning the number of
+/// bytes read.
+fn read_full(file: &mut fs::File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Print a DIFF-CHUNKS line for two files that were found to differ, with
+/// the byte ranges of the chunks whose hashes differ.
+fn print_diff_chunks(
+    orig: &Path,
+    backup: &Path,
+    orig_meta: &fs::Metadata,
+    config: &Config,
+    stats: &Stats,
+) {
+    let chunk_size = config.chunk_size;
+    let relative = orig.strip_prefix(&config.original).unwrap_or(orig);
+    let stored = config
+        .manifest
+        .as_ref()
+        .filter(|m| m.chunk_size == chunk_size)
+        .and_then(|m| m.get(relative, orig_meta))
+        .map(|e| e.chunks.clone());
+
+    let (orig_chunks, backup_chunks) = rayon::join(
+        || match stored {
+            Some(chunks) => Ok(chunks),
+            None => chunk_hashes(orig, chunk_size).map(|h| h.chunks),
+        },
+        || chunk_hashes(backup, chunk_size).map(|h| h.chunks),
+    );
+    let (orig_chunks, backup_chunks) = match (orig_chunks, backup_chunks) {
+        (Ok(o), Ok(b)) => (o, b),
+        (Err(e), _) | (_, Err(e)) => {
+            println!(
+                "ERROR: Cannot hash chunks of [{}] and [{}]: {}",
+                orig.display(),
+                backup.display(),
+                e
+            );
+            stats.inc_errors();
+            return;
+        }
+    };
+
+    // Differing chunks, merged into byte ranges. Chunks missing on one side
+    // (the file changed since it was compared) count as differing.
+    let count = orig_chunks.len().max(backup_chunks.len());
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    let mut differing = 0;
+    for i in 0..count {
+        if orig_chunks.get(i).is_some() && orig_chunks.get(i) == backup_chunks.get(i) {
+            continue;
+        }
+        differing += 1;
+        let start = i as u64 * chunk_size;
+        let end = (start + chunk_size).min(orig_meta.len().max(start));
+        match ranges.last_mut() {
+            Some((_, last_end)) if *last_end == start => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    if differing == 0 {
+        println!("DIFF-CHUNKS: [{}] no chunk differs", orig.display());
+        return;
+    }
+    println!(
+        "DIFF-CHUNKS: [{}] {} of {} chunks of {} bytes differ: {}",
+        orig.display(),
+        differing,
+        count,
+        chunk_size,
+        format_ranges(&ranges)
+    );
+}
+
+/// Byte ranges for DIFF-CHUNKS and ZEROED lines, e.g. "0..4096, 8192..9000".
+/// Only the first few are listed.
+fn format_ranges(ranges: &[(u64, u64)]) -> String {
+    const MAX_RANGES: usize = 10;
+
+    let mut listed: Vec<String> =
+        ranges.iter().take(MAX_RANGES).map(|(s, e)| format!("{}..{}", s, e)).collect();
+    if ranges.len() > MAX_RANGES {
+        listed.push("...".to_string());
+    }
+    listed.join(", ")
+}
+
+/// File extensions of compressed, encrypted and media formats, whose data
+/// looks random and practically never has long runs of zero bytes.
+const NO_ZERO_RUNS: &[&str] = &[
+    "7z", "age", "avi", "bz2", "flac", "gif", "gpg", "gz", "heic", "jpeg", "jpg", "lz4", "m4a",
+    "mkv", "mov", "mp3", "mp4", "ogg", "png", "rar", "tgz", "webm", "webp", "xz", "zip", "zst",
+];
+
+/// Look for zero-filled ranges of at least --zeroed-min bytes in the backup
+/// file, where the original has data. Prints a ZEROED line and returns true
+/// if there are any.
+///
+/// The original is only read where the backup is zero-filled. With
+/// --manifest, the original's stored chunk hashes are used instead. Only if
+/// the original can't be read are the formats in NO_ZERO_RUNS reported
+/// without it.
+fn print_zeroed(
+    orig: &Path,
+    backup: &Path,
+    orig_meta: &fs::Metadata,
+    config: &Config,
+    stats: &Stats,
+) -> bool {
+    let runs = match zero_runs(backup, config.zeroed_min) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("ERROR: Cannot read [{}]: {}", backup.display(), e);
+            stats.inc_errors();
+            return false;
+        }
+    };
+    if runs.is_empty() {
+        return false;
+    }
+
+    let relative = orig.strip_prefix(&config.original).unwrap_or(orig);
+    let stored = config
+        .manifest
+        .as_ref()
+        .and_then(|m| Some((m.chunk_size, m.get(relative, orig_meta)?)));
+
+    let mut note = String::new();
+    let zeroed = match stored {
+        Some((chunk_size, entry)) => zeroed_by_chunks(&runs, chunk_size, &entry.chunks, orig_meta.len()),
+        None => match zeroed_by_reading(&runs, orig) {
+            Ok(z) => z,
+            Err(e) => {
+                println!("ERROR: Cannot read [{}]: {}", orig.display(), e);
+                stats.inc_errors();
+
+                let extension = backup.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+                match extension.filter(|e| NO_ZERO_RUNS.contains(&e.as_str())) {
+                    Some(ext) => {
+                        note = format!(" (original unreadable, unexpected in a .{} file)", ext);
+                        runs
+                    }
+                    None => return false,
+                }
+            }
+        },
+    };
+    if zeroed.is_empty() {
+        return false;
+    }
+
+    let bytes: u64 = zeroed.iter().map(|(s, e)| e - s).sum();
+    println!(
+        "ZEROED: [{}] {} zero-filled ranges ({} bytes): {}{}",
+        backup.display(),
+        zeroed.len(),
+        bytes,
+        format_ranges(&zeroed),
+        note
+    );
+    stats.inc_zeroed();
+    true
+}
+
+/// Ranges of zero bytes in a file that are at least `min` bytes long.
+fn zero_runs(path: &Path, min: u64) -> std::io::Result<Vec<(u64, u64)>> {
+    let mut file = fs::File::open(path)?;
+    let mut buf = vec![0u8; 1 << 20];
+    let mut runs = Vec::new();
+    let mut run_start = None;
+    let mut pos = 0u64;
+
+    loop {
+        let len = read_full(&mut file, &mut buf)?;
+        for (i, &byte) in buf[..len].iter().enumerate() {
+            let offset = pos + i as u64;
+            match (byte, run_start) {
+                (0, None) => run_start = Some(offset),
+                (0, Some(_)) => {}
+                (_, Some(start)) => {
+                    if offset - start >= min {
+                        runs.push((start, offset));
+                    }
+                    run_start = None;
+                }
+                (_, None) => {}
+            }
+        }
+        pos += len as u64;
+        if len < buf.len() {
+            break;
+        }
+    }
+    if let Some(start) = run_start {
+        if pos - start >= min {
+            runs.push((start, pos));
+        }
+    }
+    Ok(runs)
+}
+
+/// The parts of `runs` where the original has non-zero bytes, by reading it.
+/// Past the end of the original there is nothing to compare with.
+fn zeroed_by_reading(runs: &[(u64, u64)], orig: &Path) -> std::io::Result<Vec<(u64, u64)>> {
+    let file = fs::File::open(orig)?;
+    let size = file.metadata()?.len();
+    let mut buf = vec![0u8; 1 << 20];
+    let mut zeroed = Vec::new();
+
+    for &(start, end) in runs {
+        let end = end.min(size);
+        let mut pos = start;
+        while pos < end {
+            let len = buf.len().min((end - pos) as usize);
+            read_exact_at(&file, &mut buf[..len], pos)?;
+            if buf[..len].iter().any(|&b| b != 0) {
+                zeroed.push((start, end));
+                break;
+            }
+            pos += len as u64;
+        }
+    }
+    Ok(zeroed)
+}
+
+/// The parts of `runs` where the original has non-zero bytes, according to
+/// its stored chunk hashes. Only chunks entirely inside a run can be told
+/// apart from the all-zero chunk, so partly covered chunks are left out.
+fn zeroed_by_chunks(
+    runs: &[(u64, u64)],
+    chunk_size: u64,
+    chunks: &[blake3::Hash],
+    size: u64,
+) -> Vec<(u64, u64)> {
+    let zero_chunk = |len: u64| {
+        let mut hasher = blake3::Hasher::new();
+        std::io::copy(&mut std::io::repeat(0).take(len), &mut hasher).unwrap();
+        hasher.finalize()
+    };
+    let full_zero_chunk = zero_chunk(chunk_size);
+
+    let mut zeroed: Vec<(u64, u64)> = Vec::new();
+    for &(start, end) in runs {
+        for i in start.div_ceil(chunk_size)..end / chunk_size + 1 {
+            let chunk_start = i * chunk_size;
+            let chunk_end = (chunk_start + chunk_size).min(size);
+            if chunk_end <= chunk_start || chunk_end > end || i as usize >= chunks.len() {
+                continue;
+            }
+            let expected = if chunk_end - chunk_start == chunk_size {
+                full_zero_chunk
+            } else {
+                zero_chunk(chunk_end - chunk_start)
+            };
+            if chunks[i as usize] == expected {
+                continue;
+            }
+            match zeroed.last_mut() {
+                Some((_, last_end)) if *last_end == chunk_start => *last_end = chunk_end,
+                _ => zeroed.push((chunk_start, chunk_end)),
+            }
+        }
+    }
+    zeroed
+}
+
+/// Offsets and lengths of the samples to compare for the original file `orig`
+/// of `size` bytes, according to --samples, --sample-size and --sample-strategy.
+fn sample_offsets(size: u64, orig: &Path, config: &Config) -> Vec<(u64, usize)> {
+    const GIB: u64 = 1 << 30;
+
+    let mut rng = sample_rng(orig, config);
+    let len = config.sample_size.min(size);
+    let max_offset = size - len;
+    let n = u64::from(config.samples);
+
+    let offsets: Vec<u64> = match config.sample_strategy {
+        SampleStrategy::Random => (0..n).map(|_| rng.random_range(0..=max_offset)).collect(),
+        SampleStrategy::Edges => {
+            let edges = [0, max_offset].into_iter().take(n as usize);
+            let random = (2..n).map(|_| rng.random_range(0..=max_offset)).collect::<Vec<_>>();
+            edges.chain(random).collect()
+        }
+        SampleStrategy::Stratified => (0..n)
+            .map(|i| {
+                // Part i covers offsets [start, end), except the last one
+                // which also includes max_offset.
+                let start = (max_offset as u128 * i as u128 / n as u128) as u64;
+                let end = (max_offset as u128 * (i + 1) as u128 / n as u128) as u64;
+                if end > start && i + 1 < n {
+                    rng.random_range(start..end)
+                } else {
+                    rng.random_range(start..=end)
+                }
+            })
+            .collect(),
+        SampleStrategy::Scaled => {
+            let count = n * size.div_ceil(GIB).max(1);
+            (0..count).map(|_| rng.random_range(0..=max_offset)).collect()
+        }
+    };
+
+    offsets.into_iter().map(|offset| (offset, len as usize)).collect()
+}
+
+/// Random number generator for the samples of `orig`, seeded from --seed and
+/// the path relative to the original root. The same file is sampled at the
+/// same offsets in every backup and on every run with the same seed.
+fn sample_rng(orig: &Path, config: &Config) -> StdRng {
+    let relative = orig.strip_prefix(&config.original).unwrap_or(orig);
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&config.seed.to_le_bytes());
+    hasher.update(relative.as_os_str().as_encoded_bytes());
+    StdRng::from_seed(*hasher.finalize().as_bytes())
+}
+
+/// Most bytes of a sample that are read from each file at a time.
+const SAMPLE_BUFFER: usize = 1 << 20;
+
+/// Why samples couldn't be compared: the error of each side that failed.
+enum SampleError {
+    Orig(std::io::Error),
+    Backup(std::io::Error),
+    Both(std::io::Error, std::io::Error),
+}
+
+impl SampleError {
+    fn of(orig: std::io::Result<()>, backup: std::io::Result<()>) -> Result<(), SampleError> {
+        match (orig, backup) {
+            (Ok(()), Ok(())) => Ok(()),
+            (Err(e), Ok(())) => Err(SampleError::Orig(e)),
+            (Ok(()), Err(e)) => Err(SampleError::Backup(e)),
+            (Err(e1), Err(e2)) => Err(SampleError::Both(e1, e2)),
+        }
+    }
+}
+
+/// Whether the samples at `offsets` match in both files, stopping at the
+/// first one that doesn't.
+///
+/// Each file is opened once, and the samples are compared one at a time in
+/// offset order, which keeps the reads sequential-ish for the OS and for
+/// network filesystems. Both sides of a sample are read concurrently, at
+/// most SAMPLE_BUFFER bytes at a time, while the kernel is asked to read
+/// ahead the next sample.
+///
 /// The caller must ensure offset + len <= file size, otherwise hitting an EOF
 /// will cause an error to be returned.
-fn read_sample(path: &Path, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
-    let mut file = fs::File::open(path)?;
+fn samples_match(orig: &Path, backup: &Path, offsets: &[(u64, usize)]) -> Result<bool, SampleError> {
+    let (orig_file, backup_file) = match (fs::File::open(orig), fs::File::open(backup)) {
+        (Ok(o), Ok(b)) => (o, b),
+        (Err(e), Ok(_)) => return Err(SampleError::Orig(e)),
+        (Ok(_), Err(e)) => return Err(SampleError::Backup(e)),
+        (Err(e1), Err(e2)) => return Err(SampleError::Both(e1, e2)),
+    };
+
+    let mut offsets = offsets.to_vec();
+    offsets.sort_by_key(|&(offset, _)| offset);
+    let buf_len = offsets.iter().map(|&(_, len)| len).max().unwrap_or(0).min(SAMPLE_BUFFER);
+    let (mut orig_buf, mut backup_buf) = (vec![0u8; buf_len], vec![0u8; buf_len]);
+
+    for (i, &(offset, len)) in offsets.iter().enumerate() {
+        if let Some(&(next, next_len)) = offsets.get(i + 1) {
+            will_need(&orig_file, next, next_len);
+            will_need(&backup_file, next, next_len);
+        }
+
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(buf_len);
+            let at = offset + done as u64;
+            let (o, b) = rayon::join(
+                || read_exact_at(&orig_file, &mut orig_buf[..n], at),
+                || read_exact_at(&backup_file, &mut backup_buf[..n], at),
+            );
+            SampleError::of(o, b)?;
+            if orig_buf[..n] != backup_buf[..n] {
+                return Ok(false);
+            }
+            done += n;
+        }
+    }
+    Ok(true)
+}
+
+/// Tell the kernel that `len` bytes at `offset` will be read soon, so it can
+/// start reading them. It's only a hint, so errors are ignored.
+#[cfg(target_os = "linux")]
+fn will_need(file: &fs::File, offset: u64, len: usize) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: posix_fadvise only reads its arguments, and the descriptor
+    // stays open for the duration of the call.
+    unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            offset as libc::off_t,
+            len as libc::off_t,
+            libc::POSIX_FADV_WILLNEED,
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn will_need(_file: &fs::File, _offset: u64, _len: usize) {}
+
+#[cfg(unix)]
+fn read_exact_at(file: &fs::File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(not(unix))]
+fn read_exact_at(mut file: &fs::File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
     file.seek(SeekFrom::Start(offset))?;
-    let mut buf = vec![0u8; len];
-    file.read_exact(&mut buf)?;
-    Ok(buf)
+    file.read_exact(buf)
+}
+
+/// hash_file() for a file in the original tree, going through the cache when
+/// there is one. Hashes are cached per version of the file (see FileVersion),
+/// so a file that changed is hashed again. Errors are not cached, so each
+/// pass reports its own.
+///
+/// With --manifest, the stored hash of an unchanged file is used instead of
+/// reading it. With --write-manifest, the file's chunks are hashed as well and
+/// stored, or the entry from --manifest is copied.
+fn hash_orig_file(path: &Path, meta: &fs::Metadata, config: &Config) -> std::io::Result<blake3::Hash> {
+    if let Some(cache) = &config.orig_cache {
+        if let Some(hash) = cache.get_hash(path, meta) {
+            return Ok(hash);
+        }
+    }
+
+    let relative = path.strip_prefix(&config.original).unwrap_or(path);
+    let stored = config.manifest.as_ref().and_then(|m| Some((m.chunk_size, m.get(relative, meta)?)));
+    let hash = match (stored, &config.manifest_out) {
+        // A stored entry is carried over to the new manifest, unless its
+        // chunks have a different size and must be hashed again.
+        (Some((chunk_size, entry)), Some((_, manifest))) if chunk_size == config.chunk_size => {
+            manifest.lock().unwrap().insert_entry(relative, entry);
+            entry.hash
+        }
+        (Some((_, entry)), None) => entry.hash,
+        (_, Some((_, manifest))) => {
+            let hashes = chunk_hashes(path, config.chunk_size)?;
+            let hash = hashes.hash;
+            manifest.lock().unwrap().insert(relative, meta, hashes);
+            hash
+        }
+        (None, None) => hash_file(path, config)?,
+    };
+
+    if let Some(cache) = &config.orig_cache {
+        cache.insert_hash(path, meta, hash);
+    }
+    Ok(hash)
 }
 
-fn hash_file(path: &Path) -> std::io::Result<blake3::Hash> {
+/// BLAKE3 hash of a file, read according to --hash-mode and --hash-threads.
+fn hash_file(path: &Path, config: &Config) -> std::io::Result<blake3::Hash> {
+    let hash = || match config.hash_mode {
+        HashMode::Mmap => hash_mmap(path),
+        HashMode::Read => hash_read(path, config.hash_buffer),
+        // Any file that can't be mapped can still be read.
+        HashMode::Auto => hash_mmap(path).or_else(|_| hash_read(path, config.hash_buffer)),
+    };
+    match &config.hash_pool {
+        Some(pool) => pool.install(hash),
+        None => hash(),
+    }
+}
+
+fn hash_mmap(path: &Path) -> std::io::Result<blake3::Hash> {
     let mut hasher = blake3::Hasher::new();
     hasher.update_mmap_rayon(path)?;
     Ok(hasher.finalize())
 }
+
+fn hash_read(path: &Path, buffer: usize) -> std::io::Result<blake3::Hash> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; buffer];
+    loop {
+        let len = read_full(&mut file, &mut buf)?;
+        hasher.update_rayon(&buf[..len]);
+        if len < buf.len() {
+            break;
+        }
+    }
+    Ok(hasher.finalize())
+}
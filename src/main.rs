@@ -1,15 +1,24 @@
 mod cli;
 mod compare;
+mod manifest;
+mod policy;
+mod profile;
 mod stats;
 
+use std::path::Path;
 use std::process;
 use std::sync::Arc;
 
 use clap::Parser;
 
 use cli::{Cli, Config};
+use compare::OrigCache;
 use stats::Stats;
 
+/// Exit code when verification stopped early because an abort threshold
+/// (--max-errors, --max-missing-percent, --fail-fast) was exceeded.
+pub const EXIT_INCOMPLETE: i32 = 3;
+
 fn main() {
     // Replace the default panic hook to handle broken pipes cleanly.
     // Rust ignores SIGPIPE, so writing to a broken pipe (e.g. piping to
@@ -37,47 +46,184 @@ fn main() {
         .collect();
     println!("CMD: {}", cmd.join(" "));
 
-    let cli = Cli::parse();
+    let (args, negated) = cli::apply_negations(std::env::args_os().collect());
+    let cli = Cli::parse_from(&args);
 
-    let config = match Config::from_cli(cli) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            process::exit(2);
+    // A profile can have several root pairs, each of which is a separate run
+    // with its own Config. Without --profile there's exactly one.
+    let clis = match &cli.profile {
+        Some(name) => {
+            let path = match cli.config.clone().or_else(profile::default_config_path) {
+                Some(p) => p,
+                None => {
+                    eprintln!("Error: Cannot find the config file, give it with --config");
+                    process::exit(2);
+                }
+            };
+            match profile::expand(name, &path, &args[1..], &negated) {
+                Ok(clis) => clis,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(2);
+                }
+            }
         }
+        None => vec![cli],
     };
 
-    if !config.original.is_dir() {
-        eprintln!("Error: {:?} is not a directory", config.original);
-        process::exit(2);
-    }
+    // Validate every run before starting the first, so a typo in the last
+    // root pair doesn't surface hours into a verification.
+    // One seed for all runs, so the seed in the final summary reproduces
+    // each of them.
+    let seed = rand::random();
+    let mut configs = Vec::new();
+    for mut cli in clis {
+        cli.seed.get_or_insert(seed);
+        let mut config = match Config::from_cli(cli) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(2);
+            }
+        };
 
-    if !config.backup.is_dir() {
-        eprintln!("Error: {:?} is not a directory", config.backup);
-        process::exit(2);
+        // Roots may be directories or files. Anything else can't be compared.
+        if !is_dir_or_file(&config.original) {
+            eprintln!("Error: {:?} is not a directory or file", config.original);
+            process::exit(2);
+        }
+
+        for root in &config.backups {
+            if !is_dir_or_file(&root.path) {
+                eprintln!("Error: {:?} is not a directory or file", root.path);
+                process::exit(2);
+            }
+
+            if config.original == root.path {
+                let kind = if root.path.is_dir() { "directory" } else { "file" };
+                eprintln!("Warning: original and backup are the same {}", kind);
+            }
+        }
+
+        // With several backups, run one pass per backup. The passes share a
+        // cache so the original's hashes and most of its metadata are only
+        // computed once.
+        if config.backups.len() > 1 {
+            config.orig_cache = Some(Arc::new(OrigCache::default()));
+        }
+        configs.push(config);
     }
 
-    if config.original == config.backup {
-        eprintln!("Warning: original and backup are the same directory");
+    // Manifests are keyed by paths relative to the original root, so one
+    // manifest can't serve several originals.
+    if configs.len() > 1 && configs.iter().any(|c| c.manifest.is_some() || c.manifest_out.is_some()) {
+        eprintln!("Error: --manifest and --write-manifest can't be used with several root pairs");
+        process::exit(2);
     }
 
-    let stats = Arc::new(Stats::new());
+    let stats: Arc<Vec<Vec<Stats>>> = Arc::new(
+        configs
+            .iter()
+            .map(|config| config.backups.iter().map(|_| Stats::new()).collect())
+            .collect(),
+    );
     let stats_ctrlc = Arc::clone(&stats);
 
     ctrlc::set_handler(move || {
         eprintln!("\nInterrupted!");
-        stats_ctrlc.eprint_summary();
+        let total = Stats::new();
+        for s in stats_ctrlc.iter().flatten() {
+            total.add(s);
+        }
+        total.eprint_summary();
         eprintln!("WARNING: EXITING BEFORE VERIFICATION WAS COMPLETE!");
         process::exit(130);
     })
     .expect("Error setting Ctrl-C handler");
 
-    compare::compare_dirs(&config, &stats);
+    // Shown before the first finding too, so that a run that is aborted or
+    // interrupted can be reproduced. All runs share the sampling.
+    if let Some(sampling) = configs[0].sampling_description() {
+        println!("SAMPLING: {}", sampling);
+    }
+
+    if let [config] = configs.as_slice() {
+        let total = run(config, &stats[0]);
 
+        println!("COMPARISON FINISHED!");
+        if config.backups.len() > 1 {
+            total.print_summary_with_heading("SUMMARY (all backups):");
+        } else {
+            total.print_summary();
+        }
+        if let Some(sampling) = config.sampling_description() {
+            println!("    Sampling: {}", sampling);
+        }
+        config.policy.print_warnings(&total);
+
+        let code = config.policy.exit_code(&total, config.exit_bitmask);
+        if code != 0 {
+            process::exit(code);
+        }
+        return;
+    }
+
+    let total = Stats::new();
+    let mut code = 0;
+    for (i, config) in configs.iter().enumerate() {
+        println!("ROOTS {} of {}: [{}]", i + 1, configs.len(), config.original.display());
+        let run_total = run(config, &stats[i]);
+        run_total.print_summary_with_heading(&format!("SUMMARY for [{}]:", config.original.display()));
+        code |= config.policy.exit_code(&run_total, config.exit_bitmask);
+        total.add(&run_total);
+    }
+
+    // All runs come from the same profile and command line, so they share
+    // one policy.
     println!("COMPARISON FINISHED!");
-    stats.print_summary();
+    total.print_summary_with_heading("SUMMARY (all roots):");
+    if let Some(sampling) = configs[0].sampling_description() {
+        println!("    Sampling: {}", sampling);
+    }
+    configs[0].policy.print_warnings(&total);
 
-    if stats.has_differences_or_weirdness() {
-        process::exit(1);
+    if code != 0 {
+        process::exit(code);
     }
 }
+
+/// Compare the original of `config` against each of its backups, and return
+/// the combined stats.
+fn run(config: &Config, stats: &[Stats]) -> Stats {
+    let multiple = config.backups.len() > 1;
+    let total = Stats::new();
+    for (i, root) in config.backups.iter().enumerate() {
+        if multiple {
+            println!("BACKUP {} of {}: [{}]", i + 1, config.backups.len(), root.path.display());
+        }
+
+        if i + 1 == config.backups.len() {
+            if let Some(cache) = &config.orig_cache {
+                cache.start_last_pass();
+            }
+        }
+        compare::compare_dirs(&config.for_backup(i), &stats[i]);
+
+        if multiple {
+            stats[i].print_summary_with_heading(&format!("SUMMARY for [{}]:", root.path.display()));
+        }
+        total.add(&stats[i]);
+    }
+
+    if let Some((path, manifest)) = &config.manifest_out {
+        if let Err(e) = manifest.lock().unwrap().save(path) {
+            println!("ERROR: {}", e);
+            total.inc_errors();
+        }
+    }
+    total
+}
+
+fn is_dir_or_file(path: &Path) -> bool {
+    path.is_dir() || path.is_file()
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use toml::{Table, Value};
+
+/// Stored BLAKE3 hashes of the original's files (--manifest, --write-manifest).
+///
+/// Files are keyed by their path relative to the original root. An entry is
+/// only trusted while the file's size and modification time are unchanged.
+pub struct Manifest {
+    pub chunk_size: u64,
+    files: HashMap<PathBuf, ManifestEntry>,
+}
+
+#[derive(Clone)]
+pub struct ManifestEntry {
+    size: u64,
+    mtime_ns: i64,
+    pub hash: blake3::Hash,
+    /// Hash of each chunk of `chunk_size` bytes, the last one possibly shorter.
+    pub chunks: Vec<blake3::Hash>,
+}
+
+impl Manifest {
+    pub fn new(chunk_size: u64) -> Manifest {
+        Manifest { chunk_size, files: HashMap::new() }
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read manifest {:?}: {}", path, e))?;
+        let bad = |what: &str| format!("{}: {}", path.display(), what);
+        let table: Table = toml::from_str(&text)
+            .map_err(|e| bad(e.to_string().trim_end()))?;
+
+        let chunk_size = table
+            .get("chunk_size")
+            .and_then(Value::as_integer)
+            .filter(|n| *n > 0)
+            .ok_or_else(|| bad("chunk_size must be a positive integer"))? as u64;
+
+        let mut manifest = Manifest::new(chunk_size);
+        let files = match table.get("files") {
+            Some(Value::Table(files)) => files,
+            None => return Ok(manifest),
+            Some(_) => return Err(bad("files must be a table")),
+        };
+        for (name, entry) in files {
+            let entry = parse_entry(entry).ok_or_else(|| bad(&format!("invalid entry for {:?}", name)))?;
+            manifest.files.insert(PathBuf::from(name), entry);
+        }
+        Ok(manifest)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut names: Vec<&PathBuf> = self.files.keys().collect();
+        names.sort();
+
+        let mut files = Table::new();
+        for name in names {
+            let entry = &self.files[name];
+            let mut t = Table::new();
+            t.insert("size".into(), Value::Integer(entry.size as i64));
+            t.insert("mtime_ns".into(), Value::Integer(entry.mtime_ns));
+            t.insert("hash".into(), Value::String(entry.hash.to_hex().to_string()));
+            t.insert(
+                "chunks".into(),
+                Value::Array(entry.chunks.iter().map(|h| Value::String(h.to_hex().to_string())).collect()),
+            );
+            // Keys are strings, so paths that aren't valid UTF-8 are left out.
+            if let Some(name) = name.to_str() {
+                files.insert(name.into(), Value::Table(t));
+            }
+        }
+
+        let mut table = Table::new();
+        table.insert("chunk_size".into(), Value::Integer(self.chunk_size as i64));
+        table.insert("files".into(), Value::Table(files));
+
+        let text = toml::to_string(&table).map_err(|e| format!("Cannot write manifest {:?}: {}", path, e))?;
+        fs::write(path, text).map_err(|e| format!("Cannot write manifest {:?}: {}", path, e))
+    }
+
+    /// The entry for `relative`, if there is one and the file hasn't changed
+    /// since it was written.
+    pub fn get(&self, relative: &Path, meta: &fs::Metadata) -> Option<&ManifestEntry> {
+        let entry = self.files.get(relative)?;
+        (entry.size == meta.len() && Some(entry.mtime_ns) == mtime_ns(meta)).then_some(entry)
+    }
+
+    pub fn insert(&mut self, relative: &Path, meta: &fs::Metadata, hashes: ChunkHashes) {
+        // Without a modification time, the entry could never be trusted.
+        let Some(mtime_ns) = mtime_ns(meta) else {
+            return;
+        };
+        let entry = ManifestEntry { size: meta.len(), mtime_ns, hash: hashes.hash, chunks: hashes.chunks };
+        self.files.insert(relative.to_path_buf(), entry);
+    }
+
+    /// Store an entry of another manifest with the same chunk size, e.g. one
+    /// from --manifest that is still valid.
+    pub fn insert_entry(&mut self, relative: &Path, entry: &ManifestEntry) {
+        self.files.insert(relative.to_path_buf(), entry.clone());
+    }
+}
+
+fn parse_entry(value: &Value) -> Option<ManifestEntry> {
+    let parse_hash = |v: &Value| blake3::Hash::from_hex(v.as_str()?).ok();
+    Some(ManifestEntry {
+        size: value.get("size")?.as_integer()?.try_into().ok()?,
+        mtime_ns: value.get("mtime_ns")?.as_integer()?,
+        hash: parse_hash(value.get("hash")?)?,
+        chunks: value.get("chunks")?.as_array()?.iter().map(parse_hash).collect::<Option<_>>()?,
+    })
+}
+
+fn mtime_ns(meta: &fs::Metadata) -> Option<i64> {
+    let since_epoch = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    since_epoch.as_nanos().try_into().ok()
+}
+
+/// BLAKE3 hash of a whole file and of each of its chunks.
+pub struct ChunkHashes {
+    pub hash: blake3::Hash,
+    pub chunks: Vec<blake3::Hash>,
+}
+
+/// Hash a file and its chunks of `chunk_size` bytes in a single read.
+pub fn chunk_hashes(path: &Path, chunk_size: u64) -> std::io::Result<ChunkHashes> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut chunks = Vec::new();
+    let mut buf = vec![0u8; chunk_size.min(1 << 20) as usize];
+
+    loop {
+        let mut chunk = blake3::Hasher::new();
+        let mut chunk_len = 0;
+        while chunk_len < chunk_size {
+            let want = buf.len().min((chunk_size - chunk_len) as usize);
+            let n = match file.read(&mut buf[..want]) {
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            chunk.update(&buf[..n]);
+            chunk_len += n as u64;
+        }
+        if chunk_len == 0 {
+            break;
+        }
+        chunks.push(chunk.finalize());
+        if chunk_len < chunk_size {
+            break;
+        }
+    }
+
+    Ok(ChunkHashes { hash: hasher.finalize(), chunks })
+}
@@ -0,0 +1,172 @@
+use clap::ValueEnum;
+
+use crate::stats::Stats;
+
+/// A category of findings that the exit-code policy can act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Category {
+    Missing,
+    Different,
+    Extras,
+    Special,
+    // Errors other than dangling symlinks and symlink cycles.
+    Errors,
+    Dangling,
+    Skipped,
+    Cycles,
+    // Entries deleted between listing their directory and reading them.
+    Vanished,
+    // Files that are the same file in the original and the backup.
+    NotIndependent,
+}
+
+impl Category {
+    pub const ALL: [Category; 10] = [
+        Category::Missing,
+        Category::Different,
+        Category::Extras,
+        Category::Special,
+        Category::Errors,
+        Category::Dangling,
+        Category::Skipped,
+        Category::Cycles,
+        Category::Vanished,
+        Category::NotIndependent,
+    ];
+
+    /// Bit set in the exit code for this category with --exit-bitmask.
+    ///
+    /// The value 2 is never used, so no combination can be mistaken for the
+    /// usage-error code 2 or EXIT_INCOMPLETE (3), and all bits together stay
+    /// below the codes shells reserve (126 and up). Error-like categories
+    /// share one bit to fit.
+    pub fn bit(self) -> i32 {
+        match self {
+            Category::Missing => 1,
+            // A backup that is the original protects it no more than a different one.
+            Category::Different | Category::NotIndependent => 4,
+            Category::Extras => 8,
+            Category::Special => 16,
+            Category::Errors | Category::Dangling | Category::Cycles | Category::Vanished => 32,
+            Category::Skipped => 64,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Missing => "missing",
+            Category::Different => "different",
+            Category::Extras => "extras",
+            Category::Special => "special",
+            Category::Errors => "errors",
+            Category::Dangling => "dangling",
+            Category::Skipped => "skipped",
+            Category::Cycles => "cycles",
+            Category::Vanished => "vanished",
+            Category::NotIndependent => "not-independent",
+        }
+    }
+}
+
+/// What a category does to the exit code when it occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Fail the run.
+    Fail,
+    /// Print a warning after the summary, but don't fail the run.
+    Warn,
+    /// Don't affect the exit code at all.
+    Ignore,
+}
+
+impl Level {
+    fn flag(self) -> &'static str {
+        match self {
+            Level::Fail => "--fail-on",
+            Level::Warn => "--warn-on",
+            Level::Ignore => "--allow",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Policy {
+    levels: [Level; Category::ALL.len()],
+}
+
+impl Default for Policy {
+    /// Everything fails the run except skips, matching vfy's historical behavior.
+    fn default() -> Self {
+        let mut levels = [Level::Fail; Category::ALL.len()];
+        levels[Category::Skipped as usize] = Level::Ignore;
+        Policy { levels }
+    }
+}
+
+impl Policy {
+    /// Build a policy from the --fail-on, --warn-on and --allow lists, on top
+    /// of the defaults. A category may only appear in one of the lists.
+    pub fn from_lists(fail_on: &[Category], warn_on: &[Category], allow: &[Category]) -> Result<Self, String> {
+        let mut policy = Policy::default();
+        let mut seen: Vec<(Category, Level)> = Vec::new();
+
+        let lists = [(fail_on, Level::Fail), (warn_on, Level::Warn), (allow, Level::Ignore)];
+        for (list, level) in lists {
+            for &cat in list {
+                if let Some(&(_, prev)) = seen.iter().find(|(c, l)| *c == cat && *l != level) {
+                    return Err(format!(
+                        "Category '{}' given to both {} and {}",
+                        cat.name(),
+                        prev.flag(),
+                        level.flag()
+                    ));
+                }
+                seen.push((cat, level));
+                policy.levels[cat as usize] = level;
+            }
+        }
+
+        Ok(policy)
+    }
+
+    pub fn level(&self, cat: Category) -> Level {
+        self.levels[cat as usize]
+    }
+
+    /// Categories at `level` that occurred at least once.
+    pub fn occurred(&self, stats: &Stats, level: Level) -> Vec<Category> {
+        Category::ALL
+            .into_iter()
+            .filter(|&cat| self.level(cat) == level && stats.count(cat) > 0)
+            .collect()
+    }
+
+    pub fn has_failures(&self, stats: &Stats) -> bool {
+        !self.occurred(stats, Level::Fail).is_empty()
+    }
+
+    /// Print a warning line for each --warn-on category that occurred.
+    pub fn print_warnings(&self, stats: &Stats) {
+        for cat in self.occurred(stats, Level::Warn) {
+            println!(
+                "WARNING: {} {} found, not failing because of --warn-on {}",
+                stats.count(cat),
+                cat.name(),
+                cat.name()
+            );
+        }
+    }
+
+    /// Exit code for a completed run: 0 if nothing failed, otherwise 1, or
+    /// with `bitmask` the OR of the bits of every failing category.
+    pub fn exit_code(&self, stats: &Stats, bitmask: bool) -> i32 {
+        let failing = self.occurred(stats, Level::Fail);
+        if failing.is_empty() {
+            0
+        } else if bitmask {
+            failing.iter().fold(0, |code, cat| code | cat.bit())
+        } else {
+            1
+        }
+    }
+}
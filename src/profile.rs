@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, Parser};
+use toml::{Spanned, Value};
+
+use crate::cli::{normalize_path, Cli};
+
+/// Profile keys and their values, with spans so errors can point at lines.
+type ProfileTable = BTreeMap<Spanned<String>, Spanned<Value>>;
+
+/// The whole config file: `[profiles.NAME]` tables and nothing else.
+type ConfigFile = BTreeMap<String, BTreeMap<String, ProfileTable>>;
+
+/// Default location of the profiles file: $XDG_CONFIG_HOME/vfy/profiles.toml,
+/// falling back to ~/.config/vfy/profiles.toml.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(config_dir.join("vfy").join("profiles.toml"))
+}
+
+/// Expand `--profile NAME` into one Cli per root pair of the profile.
+///
+/// Each profile key is turned into the long option of the same name, and the
+/// result is parsed by clap exactly like a command line:
+///
+///     vfy <profile options> <original> <backups...> <command-line args>
+///
+/// `cmdline` is the command line without the program name, after
+/// apply_negations(), and `negated` the flags it turned off. Options given on
+/// the command line replace the profile's (list options like --ignore are
+/// added to the profile's). If the command line has its own roots, they
+/// replace the profile's roots.
+///
+/// With several root pairs, profile-wide and command-line ignores only go to
+/// the pairs they are in, while the ignores of a [[roots]] table only go to
+/// that pair.
+pub fn expand(name: &str, path: &Path, cmdline: &[OsString], negated: &[String]) -> Result<Vec<Cli>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read config file {:?}: {}", path, e))?;
+    let mut file: ConfigFile = toml::from_str(&text)
+        .map_err(|e| format!("{}: {}", path.display(), e.to_string().trim_end()))?;
+
+    if let Some(key) = file.keys().find(|k| *k != "profiles") {
+        return Err(format!(
+            "{}: unknown top-level table [{}], expected [profiles.NAME]",
+            path.display(),
+            key
+        ));
+    }
+
+    let mut profiles = file.remove("profiles").unwrap_or_default();
+    let profile = profiles.remove(name).ok_or_else(|| {
+        let known: Vec<&String> = profiles.keys().collect();
+        format!("No profile named {:?} in {} (profiles: {:?})", name, path.display(), known)
+    })?;
+
+    let at = |span: std::ops::Range<usize>| format!("{}:{}", path.display(), line_of(&text, span.start));
+
+    let mut options: Vec<ProfileOption> = Vec::new();
+    let mut roots: Vec<Root> = Vec::new();
+    let mut top_level_root = RootBuilder::default();
+
+    for (key, value) in &profile {
+        match key.get_ref().as_str() {
+            "original" => top_level_root.original = Some(string_arg(value, &at)?),
+            "backup" | "backups" => top_level_root.backups.extend(string_list_arg(value, &at)?),
+            "roots" => {
+                let Value::Array(tables) = value.get_ref() else {
+                    return Err(format!("{}: roots must be an array of tables ([[profiles.{}.roots]])", at(value.span()), name));
+                };
+                for table in tables {
+                    roots.push(root_from_table(table, &at(value.span()))?);
+                }
+            }
+            _ => options.push(option_args(key, value, &at)?),
+        }
+    }
+
+    if top_level_root.original.is_some() || !top_level_root.backups.is_empty() {
+        if !roots.is_empty() {
+            return Err(format!(
+                "{}: profile {:?} has both original/backups and [[roots]], use one or the other",
+                path.display(),
+                name
+            ));
+        }
+        roots.push(top_level_root.build(&format!("{}: profile {:?}", path.display(), name))?);
+    }
+
+    let argv = |args: &[OsString]| std::iter::once(OsString::from("vfy")).chain(args.iter().cloned()).collect::<Vec<_>>();
+    let matches = Cli::command().try_get_matches_from(argv(cmdline)).map_err(|e| clap_message(&e))?;
+    let on_cmdline = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    if on_cmdline("original") {
+        roots = vec![Root::default()];
+    } else if roots.is_empty() {
+        return Err(format!(
+            "{}: profile {:?} has no original/backups or [[roots]], and none were given on the command line",
+            path.display(),
+            name
+        ));
+    }
+
+    // Drop what the command line turns off or replaces. Single-value options
+    // would be overridden anyway, but counts like --verbose would add up.
+    let mut options: Vec<ProfileOption> = options
+        .into_iter()
+        .filter(|o| !negated.contains(&o.id) && (o.is_list || !on_cmdline(&o.id)))
+        .collect();
+    options.sort_by_key(|o| o.offset);
+    let option_args: Vec<OsString> = options.iter().flat_map(|o| o.args.iter().cloned()).collect();
+
+    let mut clis = roots
+        .iter()
+        .map(|root| {
+            let args: Vec<OsString> = option_args.iter().chain(&root.args).chain(cmdline).cloned().collect();
+            Cli::try_parse_from(argv(&args)).map_err(|e| match invalid_option(&options) {
+                Some((option, e)) => format!("{}: {}", option.at, clap_message(&e)),
+                None => clap_message(&e),
+            })
+        })
+        .collect::<Result<Vec<Cli>, String>>()?;
+
+    if let [first, _, ..] = clis.as_slice() {
+        if let Some(ignore) = first.ignore.iter().find(|p| !clis.iter().any(|cli| within_roots(p, cli))) {
+            return Err(format!("Ignore path {:?} is not within any root pair of profile {:?}", ignore, name));
+        }
+        for cli in &mut clis {
+            let ignore = std::mem::take(&mut cli.ignore);
+            cli.ignore = ignore.into_iter().filter(|p| within_roots(p, cli)).collect();
+        }
+    }
+
+    for (cli, root) in clis.iter_mut().zip(roots) {
+        cli.ignore.extend(root.ignore);
+    }
+    Ok(clis)
+}
+
+/// Whether `path` is inside the original or one of the backups of `cli`,
+/// either as typed or after resolving symlinks in the root.
+fn within_roots(path: &Path, cli: &Cli) -> bool {
+    let Ok(cwd) = std::env::current_dir() else {
+        return true;
+    };
+    let absolute = |p: &Path| normalize_path(&cwd.join(p));
+    let path = absolute(path);
+    cli.original.iter().chain(&cli.backups).any(|root| {
+        path.starts_with(absolute(root)) || root.canonicalize().is_ok_and(|r| path.starts_with(r))
+    })
+}
+
+/// The arguments for one profile key, and the id of the option they set.
+struct ProfileOption {
+    id: String,
+    args: Vec<OsString>,
+    /// Added to the command line's values instead of being replaced by them.
+    is_list: bool,
+    /// Where the key is in the file, as a byte offset and as "file:line".
+    offset: usize,
+    at: String,
+}
+
+/// Arguments for one profile key. Their values are checked by clap together
+/// with the rest of the profile, see invalid_option().
+fn option_args(
+    key: &Spanned<String>,
+    value: &Spanned<Value>,
+    at: &impl Fn(std::ops::Range<usize>) -> String,
+) -> Result<ProfileOption, String> {
+    // Accept both `one_filesystem` and `one-filesystem`.
+    let long = key.get_ref().replace('_', "-");
+    let command = Cli::command();
+    let arg = command
+        .get_arguments()
+        .find(|a| a.get_long() == Some(long.as_str()))
+        .filter(|_| !["help", "profile", "config"].contains(&long.as_str()))
+        .ok_or_else(|| format!("{}: unknown key {:?}", at(key.span()), key.get_ref()))?;
+    let id = arg.get_id().to_string();
+    let is_list = matches!(arg.get_action(), ArgAction::Append);
+
+    let flag = format!("--{}", long);
+    let mut args: Vec<OsString> = Vec::new();
+    match (arg.get_action(), value.get_ref()) {
+        (ArgAction::SetTrue, Value::Boolean(b)) => {
+            if *b {
+                args.push(flag.into());
+            }
+        }
+        (ArgAction::SetTrue, _) => {
+            return Err(format!("{}: {} must be true or false", at(value.span()), key.get_ref()));
+        }
+        (ArgAction::Count, Value::Integer(n)) if *n >= 0 => {
+            args.extend(std::iter::repeat_n(OsString::from(&flag), *n as usize));
+        }
+        (ArgAction::Count, _) => {
+            return Err(format!("{}: {} must be a non-negative integer", at(value.span()), key.get_ref()));
+        }
+        (ArgAction::Append, Value::Array(items)) => {
+            for item in items {
+                args.push(flag.clone().into());
+                args.push(scalar(item).ok_or_else(|| {
+                    format!("{}: {} must be a list of strings or numbers", at(value.span()), key.get_ref())
+                })?);
+            }
+        }
+        (_, v) => {
+            args.push(flag.into());
+            args.push(scalar(v).ok_or_else(|| {
+                format!("{}: {} must be a string or number", at(value.span()), key.get_ref())
+            })?);
+        }
+    }
+
+    Ok(ProfileOption { id, args, is_list, offset: key.span().start, at: at(value.span()) })
+}
+
+/// The first of the profile's `options` (in file order) that clap rejects
+/// together with the ones before it, and clap's error.
+///
+/// Used to point at a line once the whole command line failed to parse:
+/// values (numbers, category names, ...) are only checked by clap, and
+/// options like --follow-within-root can't be checked without --follow.
+fn invalid_option(options: &[ProfileOption]) -> Option<(&ProfileOption, clap::Error)> {
+    let mut args: Vec<OsString> = ["vfy", "/original", "/backup"].map(OsString::from).into();
+    options.iter().find_map(|option| {
+        args.extend(option.args.iter().cloned());
+        Cli::command().try_get_matches_from(&args).err().map(|e| (option, e))
+    })
+}
+
+/// One root pair: its original and backups as arguments, and the ignores of
+/// its [[roots]] table.
+#[derive(Default)]
+struct Root {
+    args: Vec<OsString>,
+    ignore: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct RootBuilder {
+    original: Option<OsString>,
+    backups: Vec<OsString>,
+    ignore: Vec<PathBuf>,
+}
+
+impl RootBuilder {
+    fn build(self, context: &str) -> Result<Root, String> {
+        let original = self.original.ok_or_else(|| format!("{}: missing original", context))?;
+        if self.backups.is_empty() {
+            return Err(format!("{}: missing backups", context));
+        }
+        Ok(Root {
+            args: std::iter::once(original).chain(self.backups).collect(),
+            ignore: self.ignore,
+        })
+    }
+}
+
+fn root_from_table(table: &Value, context: &str) -> Result<Root, String> {
+    let Value::Table(table) = table else {
+        return Err(format!("{}: each entry of roots must be a table", context));
+    };
+
+    let mut root = RootBuilder::default();
+    for (key, value) in table {
+        let bad = || format!("{}: roots.{} must be a string (or list of strings for backups and ignore)", context, key);
+        match (key.as_str(), value) {
+            ("original", v) => root.original = Some(scalar(v).ok_or_else(bad)?),
+            ("backup" | "backups", Value::Array(items)) => {
+                for item in items {
+                    root.backups.push(scalar(item).ok_or_else(bad)?);
+                }
+            }
+            ("backup" | "backups", v) => root.backups.push(scalar(v).ok_or_else(bad)?),
+            ("ignore", Value::Array(items)) => {
+                for item in items {
+                    root.ignore.push(item.as_str().ok_or_else(bad)?.into());
+                }
+            }
+            ("ignore", v) => root.ignore.push(v.as_str().ok_or_else(bad)?.into()),
+            _ => {
+                return Err(format!(
+                    "{}: unknown key {:?} in roots, expected original, backups and ignore",
+                    context, key
+                ))
+            }
+        }
+    }
+    root.build(&format!("{}: roots", context))
+}
+
+fn string_arg(
+    value: &Spanned<Value>,
+    at: &impl Fn(std::ops::Range<usize>) -> String,
+) -> Result<OsString, String> {
+    match value.get_ref() {
+        Value::String(s) => Ok(s.into()),
+        _ => Err(format!("{}: expected a string", at(value.span()))),
+    }
+}
+
+fn string_list_arg(
+    value: &Spanned<Value>,
+    at: &impl Fn(std::ops::Range<usize>) -> String,
+) -> Result<Vec<OsString>, String> {
+    match value.get_ref() {
+        Value::String(s) => Ok(vec![s.into()]),
+        Value::Array(items) if items.iter().all(|i| i.is_str()) => {
+            Ok(items.iter().filter_map(|i| i.as_str()).map(OsString::from).collect())
+        }
+        _ => Err(format!("{}: expected a string or a list of strings", at(value.span()))),
+    }
+}
+
+/// Command-line form of a scalar TOML value. Tables, arrays and booleans
+/// have no such form.
+fn scalar(value: &Value) -> Option<OsString> {
+    match value {
+        Value::String(s) => Some(s.into()),
+        Value::Integer(i) => Some(i.to_string().into()),
+        Value::Float(f) => Some(f.to_string().into()),
+        Value::Datetime(d) => Some(d.to_string().into()),
+        Value::Boolean(_) | Value::Array(_) | Value::Table(_) => None,
+    }
+}
+
+/// 1-based line number of a byte offset.
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// A clap error on one line, without its "error: " prefix and its usage,
+/// e.g. "the following required arguments were not provided: --follow".
+fn clap_message(e: &clap::Error) -> String {
+    let text = e.to_string();
+    let message = text.lines().map(str::trim).take_while(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+    message.strip_prefix("error: ").unwrap_or(&message).to_string()
+}
@@ -1,6 +1,13 @@
 use std::fmt;
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use crate::policy::{Category, Policy};
+
+/// Number of original items that must have been processed before
+/// --max-missing-percent is checked, so that a handful of missing items at the
+/// start of the walk don't abort the whole run.
+const MIN_ITEMS_FOR_MISSING_PERCENT: u64 = 100;
+
 /// Counts are currently not mutually-exclusive, i.e. it won't add up to 100%
 /// and may not correspond to real items, e.g. when one side is ignored, we
 /// output a SKIP for the opposite-side path regardless of whether it exists.
@@ -14,6 +21,25 @@ pub struct Stats {
     special_files: AtomicU64,
     skipped: AtomicU64,
     errors: AtomicU64,
+    /// Dangling symlinks. These are also counted in `errors`.
+    dangling: AtomicU64,
+    /// Symlinks to an ancestor directory (with --follow). These are also counted in `errors`.
+    symlink_cycles: AtomicU64,
+    vanished: AtomicU64,
+    /// Followed backup symlinks that resolve outside the backup root.
+    symlinks_outside_backup: AtomicU64,
+    /// Followed backup symlinks that resolve into the original root. These
+    /// are also counted in `errors`.
+    symlinks_into_original: AtomicU64,
+    /// Files that are the same file (dev, ino) in the original and backup.
+    not_independent: AtomicU64,
+    allowed_growth: AtomicU64,
+    zeroed: AtomicU64,
+    changed_during_verify: AtomicU64,
+    // Classification of differences against the previous backup (--previous).
+    changed_at_source: AtomicU64,
+    corrupted_in_backup: AtomicU64,
+    new_since_last_backup: AtomicU64,
 }
 
 impl Stats {
@@ -28,6 +54,18 @@ impl Stats {
             special_files: AtomicU64::new(0),
             skipped: AtomicU64::new(0),
             errors: AtomicU64::new(0),
+            dangling: AtomicU64::new(0),
+            symlink_cycles: AtomicU64::new(0),
+            vanished: AtomicU64::new(0),
+            symlinks_outside_backup: AtomicU64::new(0),
+            symlinks_into_original: AtomicU64::new(0),
+            not_independent: AtomicU64::new(0),
+            allowed_growth: AtomicU64::new(0),
+            zeroed: AtomicU64::new(0),
+            changed_during_verify: AtomicU64::new(0),
+            changed_at_source: AtomicU64::new(0),
+            corrupted_in_backup: AtomicU64::new(0),
+            new_since_last_backup: AtomicU64::new(0),
         }
     }
 
@@ -67,7 +105,120 @@ impl Stats {
         self.errors.fetch_add(1, Ordering::Relaxed);
     }
 
-    fn format_summary(&self) -> String {
+    /// Count a dangling symlink. Also counts it as an error.
+    pub fn inc_dangling(&self) {
+        self.dangling.fetch_add(1, Ordering::Relaxed);
+        self.inc_errors();
+    }
+
+    /// Count a symlink cycle. Also counts it as an error.
+    pub fn inc_symlink_cycles(&self) {
+        self.symlink_cycles.fetch_add(1, Ordering::Relaxed);
+        self.inc_errors();
+    }
+
+    /// Count an entry that was deleted after its directory was listed. Not
+    /// an error, so it has its own category.
+    pub fn inc_vanished(&self) {
+        self.vanished.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_symlinks_outside_backup(&self) {
+        self.symlinks_outside_backup.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a backup symlink into the original. Also counts it as an error.
+    pub fn inc_symlinks_into_original(&self) {
+        self.symlinks_into_original.fetch_add(1, Ordering::Relaxed);
+        self.inc_errors();
+    }
+
+    pub fn inc_not_independent(&self) {
+        self.not_independent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a file that grew since the backup, allowed by --allow-growth. It
+    /// is also counted as a similarity.
+    pub fn inc_allowed_growth(&self) {
+        self.allowed_growth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a backup file with zeroed ranges. The caller counts it as
+    /// different.
+    pub fn inc_zeroed(&self) {
+        self.zeroed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a file that changed while it was compared. Also counts it as
+    /// skipped.
+    pub fn inc_changed_during_verify(&self) {
+        self.changed_during_verify.fetch_add(1, Ordering::Relaxed);
+        self.inc_skipped();
+    }
+
+    pub fn inc_changed_at_source(&self) {
+        self.changed_at_source.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_corrupted_in_backup(&self) {
+        self.corrupted_in_backup.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_new_since_last_backup(&self) {
+        self.new_since_last_backup.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count for one exit-code policy category.
+    pub fn count(&self, cat: Category) -> u64 {
+        match cat {
+            Category::Missing => self.missing.load(Ordering::Relaxed),
+            Category::Different => self.different.load(Ordering::Relaxed),
+            Category::Extras => self.extras.load(Ordering::Relaxed),
+            Category::Special => self.special_files.load(Ordering::Relaxed),
+            Category::Errors => {
+                self.errors.load(Ordering::Relaxed)
+                    - self.dangling.load(Ordering::Relaxed)
+                    - self.symlink_cycles.load(Ordering::Relaxed)
+            }
+            Category::Dangling => self.dangling.load(Ordering::Relaxed),
+            Category::Skipped => self.skipped.load(Ordering::Relaxed),
+            Category::Cycles => self.symlink_cycles.load(Ordering::Relaxed),
+            Category::Vanished => self.vanished.load(Ordering::Relaxed),
+            Category::NotIndependent => self.not_independent.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Add all of `other`'s counts to this one.
+    pub fn add(&self, other: &Stats) {
+        let pairs = [
+            (&self.original_items, &other.original_items),
+            (&self.backup_items, &other.backup_items),
+            (&self.missing, &other.missing),
+            (&self.different, &other.different),
+            (&self.similarities, &other.similarities),
+            (&self.extras, &other.extras),
+            (&self.special_files, &other.special_files),
+            (&self.skipped, &other.skipped),
+            (&self.errors, &other.errors),
+            (&self.dangling, &other.dangling),
+            (&self.symlink_cycles, &other.symlink_cycles),
+            (&self.vanished, &other.vanished),
+            (&self.symlinks_outside_backup, &other.symlinks_outside_backup),
+            (&self.symlinks_into_original, &other.symlinks_into_original),
+            (&self.not_independent, &other.not_independent),
+            (&self.allowed_growth, &other.allowed_growth),
+            (&self.zeroed, &other.zeroed),
+            (&self.changed_during_verify, &other.changed_during_verify),
+            (&self.changed_at_source, &other.changed_at_source),
+            (&self.corrupted_in_backup, &other.corrupted_in_backup),
+            (&self.new_since_last_backup, &other.new_since_last_backup),
+        ];
+        for (mine, theirs) in pairs {
+            mine.fetch_add(theirs.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    fn format_summary(&self, heading: &str) -> String {
         let orig = self.original_items.load(Ordering::Relaxed);
         let missing = self.missing.load(Ordering::Relaxed);
         let different = self.different.load(Ordering::Relaxed);
@@ -82,8 +233,8 @@ impl Stats {
             0.0
         };
 
-        format!(
-            "SUMMARY:\n\
+        let mut summary = format!(
+            "{}\n\
              \x20   Original items processed: {}\n\
              \x20   Backup items processed: {}\n\
              \x20   Missing: {} ({:.2}%)\n\
@@ -92,7 +243,11 @@ impl Stats {
              \x20   Special files: {}\n\
              \x20   Similarities: {}\n\
              \x20   Skipped: {}\n\
-             \x20   Errors: {}",
+             \x20   Errors: {}\n\
+             \x20   Dangling symlinks: {}\n\
+             \x20   Symlink cycles: {}\n\
+             \x20   Vanished: {}",
+            heading,
             orig,
             self.backup_items.load(Ordering::Relaxed),
             missing, missing_pct,
@@ -102,34 +257,156 @@ impl Stats {
             self.similarities.load(Ordering::Relaxed),
             self.skipped.load(Ordering::Relaxed),
             self.errors.load(Ordering::Relaxed),
-        )
+            self.dangling.load(Ordering::Relaxed),
+            self.symlink_cycles.load(Ordering::Relaxed),
+            self.vanished.load(Ordering::Relaxed),
+        );
+
+        // Only possible with --follow, and rare enough to only show when found.
+        let outside = self.symlinks_outside_backup.load(Ordering::Relaxed);
+        let into_original = self.symlinks_into_original.load(Ordering::Relaxed);
+        if outside > 0 {
+            summary.push_str(&format!("\n    Backup symlinks outside the backup: {}", outside));
+        }
+        if into_original > 0 {
+            summary.push_str(&format!(
+                "\n\
+                 \x20   Backup symlinks into the original: {}\n\
+                 \x20   WARNING: Content behind these symlinks was compared with itself!",
+                into_original,
+            ));
+        }
+
+        let not_independent = self.not_independent.load(Ordering::Relaxed);
+        if not_independent > 0 {
+            summary.push_str(&format!(
+                "\n\
+                 \x20   Not independent: {}\n\
+                 \x20   WARNING: These files are shared by the original and backup (hardlinks\n\
+                 \x20            or bind mounts), so the backup doesn't protect them!",
+                not_independent,
+            ));
+        }
+
+        let allowed_growth = self.allowed_growth.load(Ordering::Relaxed);
+        if allowed_growth > 0 {
+            summary.push_str(&format!("\n    Allowed growth: {}", allowed_growth));
+        }
+
+        let zeroed = self.zeroed.load(Ordering::Relaxed);
+        if zeroed > 0 {
+            summary.push_str(&format!("\n    Zeroed files: {}", zeroed));
+        }
+
+        let changed_during_verify = self.changed_during_verify.load(Ordering::Relaxed);
+        if changed_during_verify > 0 {
+            summary.push_str(&format!("\n    Changed during verify: {}", changed_during_verify));
+        }
+
+        // Only present with --previous, and only worth showing when something
+        // was classified.
+        let changed = self.changed_at_source.load(Ordering::Relaxed);
+        let corrupted = self.corrupted_in_backup.load(Ordering::Relaxed);
+        let new = self.new_since_last_backup.load(Ordering::Relaxed);
+        if changed + corrupted + new > 0 {
+            summary.push_str(&format!(
+                "\n\
+                 \x20   Changed at source: {}\n\
+                 \x20   Corrupted in backup: {}\n\
+                 \x20   New since last backup: {}",
+                changed, corrupted, new,
+            ));
+        }
+
+        summary
     }
 
     pub fn print_summary(&self) {
-        println!("{}", self.format_summary());
+        self.print_summary_with_heading("SUMMARY:");
+    }
+
+    /// Print summary under a different heading than "SUMMARY:", e.g. to tell
+    /// the per-backup summaries apart when verifying several backups.
+    pub fn print_summary_with_heading(&self, heading: &str) {
+        println!("{}", self.format_summary(heading));
     }
 
     /// Print summary to stderr (for ctrl-c handler when stdout may be broken).
     pub fn eprint_summary(&self) {
-        eprintln!("{}", self.format_summary());
+        eprintln!("{}", self.format_summary("SUMMARY:"));
     }
 
-    pub fn has_differences_or_weirdness(&self) -> bool {
-        self.missing.load(Ordering::Relaxed) > 0
-            || self.different.load(Ordering::Relaxed) > 0
-            || self.extras.load(Ordering::Relaxed) > 0
-            || self.special_files.load(Ordering::Relaxed) > 0
-            || self.errors.load(Ordering::Relaxed) > 0
+    /// Check the counts so far against the abort thresholds. Returns a
+    /// description of the first exceeded threshold, if any.
+    pub fn exceeded_threshold(
+        &self,
+        max_errors: Option<u64>,
+        max_missing_percent: Option<f64>,
+        fail_fast: bool,
+        policy: &Policy,
+    ) -> Option<String> {
+        let errors = self.errors.load(Ordering::Relaxed);
+        if let Some(max) = max_errors {
+            if errors > max {
+                return Some(format!("{} errors exceeds --max-errors {}", errors, max));
+            }
+        }
+
+        if let Some(max_pct) = max_missing_percent {
+            let orig = self.original_items.load(Ordering::Relaxed);
+            let missing = self.missing.load(Ordering::Relaxed);
+            if orig >= MIN_ITEMS_FOR_MISSING_PERCENT {
+                let pct = (missing as f64 / orig as f64) * 100.0;
+                if pct > max_pct {
+                    return Some(format!(
+                        "{} of {} original items missing ({:.2}%) exceeds --max-missing-percent {}",
+                        missing, orig, pct, max_pct
+                    ));
+                }
+            }
+        }
+
+        if fail_fast && policy.has_failures(self) {
+            return Some("difference found with --fail-fast".to_string());
+        }
+
+        None
     }
 }
 
 #[derive(Debug, Default)]
 pub struct DiffReasons {
     pub size: bool,
+    /// How the sizes relate, with --check-prefix.
+    pub size_change: Option<SizeChange>,
     pub sample: bool,
     pub hash: bool,
 }
 
+/// How a file of a different size relates to the original (--check-prefix).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeChange {
+    /// The backup is the start of the original, and not older than it.
+    Truncated,
+    /// The backup is the start of the original, which was modified after it.
+    Grown,
+    /// The original is the start of the backup.
+    Appended,
+    /// Neither file is the start of the other.
+    Mismatch,
+}
+
+impl SizeChange {
+    pub fn name(self) -> &'static str {
+        match self {
+            SizeChange::Truncated => "TRUNCATED",
+            SizeChange::Grown => "GROWN",
+            SizeChange::Appended => "APPENDED",
+            SizeChange::Mismatch => "MISMATCH",
+        }
+    }
+}
+
 impl DiffReasons {
     pub fn any(&self) -> bool {
         self.size || self.sample || self.hash
@@ -142,6 +419,9 @@ impl fmt::Display for DiffReasons {
         if self.size {
             parts.push("SIZE");
         }
+        if let Some(change) = self.size_change {
+            parts.push(change.name());
+        }
         if self.sample {
             parts.push("SAMPLE");
         }
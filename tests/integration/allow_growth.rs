@@ -0,0 +1,84 @@
+//! Tests for --allow-growth.
+
+use super::harness::setup_file_pair;
+use super::{cmd, some_line_has, stdout_of};
+use predicates::prelude::*;
+
+#[test]
+fn grown_log_is_similar() {
+    let (_tmp, a, b) = setup_file_pair("logs/app.log", b"line 1\nline 2\n", b"line 1\n");
+    for checks in [&[][..], &["--samples", "4"][..], &["--all"][..]] {
+        let assert = cmd()
+            .args([&a, &b, "--allow-growth", "*.log"])
+            .args(checks)
+            .assert()
+            .success();
+        let output = stdout_of(&assert);
+        assert!(
+            output.contains(&format!(
+                "ALLOWED-GROWTH: [{}/logs/app.log] (original 14 bytes, backup 7 bytes)\n",
+                a
+            )),
+            "got:\n{}",
+            output
+        );
+        assert!(output.contains("    Allowed growth: 1\n"), "got:\n{}", output);
+        assert!(!output.contains("DIFFERENT-FILE"), "got:\n{}", output);
+    }
+}
+
+#[test]
+fn rewritten_prefix_is_different() {
+    let (_tmp, a, b) = setup_file_pair("logs/app.log", b"line 1\nline 2\n", b"LINE 1\n");
+    let assert = cmd().args([&a, &b, "--allow-growth", "*.log", "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [SIZE]:", "logs/app.log"), "got:\n{}", output);
+    assert!(!output.contains("ALLOWED-GROWTH"), "got:\n{}", output);
+}
+
+#[test]
+fn rewritten_prefix_is_different_without_checks() {
+    // Without --samples or --all the prefix is still hashed.
+    let (_tmp, a, b) = setup_file_pair("logs/app.log", b"line 1\nline 2\n", b"LINE 1\n");
+    let assert = cmd().args([&a, &b, "--allow-growth", "*.log"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [SIZE]:", "logs/app.log"), "got:\n{}", output);
+    assert!(!output.contains("ALLOWED-GROWTH"), "got:\n{}", output);
+}
+
+#[test]
+fn shrunk_log_is_different() {
+    let (_tmp, a, b) = setup_file_pair("logs/app.log", b"line 1\n", b"line 1\nline 2\n");
+    cmd()
+        .args([&a, &b, "--allow-growth", "*.log", "--all"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("DIFFERENT-FILE [SIZE]:"));
+}
+
+#[test]
+fn only_matching_files() {
+    let (_tmp, a, b) = setup_file_pair("logs/app.txt", b"line 1\nline 2\n", b"line 1\n");
+    cmd()
+        .args([&a, &b, "--allow-growth", "*.log"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("DIFFERENT-FILE [SIZE]:"));
+}
+
+#[test]
+fn pattern_with_slash_matches_relative_path() {
+    let (_tmp, a, b) = setup_file_pair("logs/app.log", b"line 1\nline 2\n", b"line 1\n");
+    cmd().args([&a, &b, "--allow-growth", "logs/*"]).assert().success();
+    cmd().args([&a, &b, "--allow-growth", "*"]).assert().success();
+    cmd().args([&a, &b, "--allow-growth", "other/*"]).assert().code(1);
+    // '*' doesn't match across '/'.
+    cmd().args([&a, &b, "--allow-growth", "*.log/x"]).assert().code(1);
+    cmd().args([&a, &b, "--allow-growth", "l?gs/app.???"]).assert().success();
+}
+
+#[test]
+fn absolute_pattern_exits_2() {
+    let (_tmp, a, b) = setup_file_pair("logs/app.log", b"x", b"x");
+    cmd().args([&a, &b, "--allow-growth", "/logs/*"]).assert().code(2);
+}
@@ -0,0 +1,133 @@
+//! Tests for backup symlinks that, with --follow, resolve outside the backup
+//! (SYMLINK-OUTSIDE-BACKUP) or into the original (SYMLINK-INTO-ORIGINAL).
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use crate::case;
+
+// Both sides point into the original, e.g. a copy of an absolute symlink, or
+// a "backup" made with `cp -s`. The resolved files are the very same files.
+case!(backup_symlink_into_original {
+    orig: [
+        Dir("real"),
+        File("real/f.txt", "x"),
+        Sym("link", "../a/real"),
+    ],
+    backup: [
+        Dir("real"),
+        File("real/f.txt", "x"),
+        Sym("link", "../a/real"),
+    ],
+    flags: ["--follow"],
+    lines: [
+        "SYMLINK-INTO-ORIGINAL: b/link",
+        "NOT-INDEPENDENT: a/link/f.txt",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [
+        "Backup symlinks into the original: 1",
+        "WARNING: Content behind these symlinks was compared with itself!",
+        "Not independent: 1",
+    ],
+    output_excludes: [],
+    // root + real + real/f.txt + link + resolved link + link/f.txt
+    original_processed: 6,
+    backup_processed: 6,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    // Everything but link/f.txt, which is NOT-INDEPENDENT
+    similarities: 5,
+    skipped: 0,
+    errors: 1,
+    symmetric: false,
+});
+
+// Without --follow, the symlinks aren't resolved, so there's nothing to check.
+case!(backup_symlink_into_original_no_follow {
+    orig: [Sym("link", "../a")],
+    backup: [Sym("link", "../a")],
+    flags: [],
+    lines: ["SYMLINK-SKIPPED: a/link"],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["SYMLINK-INTO-ORIGINAL", "Backup symlinks"],
+    original_processed: 2,
+    backup_processed: 2,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 1,
+    errors: 0,
+    symmetric: false,
+});
+
+#[test]
+fn extra_symlink_outside_backup_warns() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[Sym("link", "../outside")]);
+    std::fs::create_dir_all(tmp.path().join("outside")).unwrap();
+
+    let assert = cmd().args([&a, &b, "--follow"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "SYMLINK-OUTSIDE-BACKUP:", "outside]"),
+        "got:\n{}",
+        output
+    );
+    assert!(output.contains("Backup symlinks outside the backup: 1"), "got:\n{}", output);
+}
+
+#[test]
+fn symlink_outside_like_original_is_not_reported() {
+    let entries = &[Sym("link", "../outside")];
+    let (tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    std::fs::create_dir_all(tmp.path().join("outside")).unwrap();
+
+    let assert = cmd().args([&a, &b, "--follow"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("SYMLINK-OUTSIDE-BACKUP"), "got:\n{}", output);
+    assert!(!output.contains("Backup symlinks outside"), "got:\n{}", output);
+}
+
+#[test]
+fn symlink_outside_unlike_original_warns() {
+    let (tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("link"), File("link/f.txt", "x")],
+        &[Sym("link", "../outside")],
+    );
+    std::fs::create_dir_all(tmp.path().join("outside")).unwrap();
+    std::fs::write(tmp.path().join("outside/f.txt"), "x").unwrap();
+
+    let assert = cmd().args([&a, &b, "--follow"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "SYMLINK-OUTSIDE-BACKUP:", "link]"), "got:\n{}", output);
+}
+
+#[test]
+fn refused_symlink_into_original_is_not_reported() {
+    let entries = &[Dir("real"), File("real/f.txt", "x"), Sym("link", "../a/real")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+
+    // The link resolves to a directory, so it isn't followed.
+    let assert = cmd().args([&a, &b, "--follow", "--follow-files-only"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "SYMLINK-SKIPPED:", "link]"), "got:\n{}", output);
+    assert!(!output.contains("SYMLINK-INTO-ORIGINAL"), "got:\n{}", output);
+    assert!(!output.contains("Backup symlinks into the original"), "got:\n{}", output);
+}
+
+#[test]
+fn refused_extra_symlink_outside_backup_is_not_reported() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[Sym("link", "../outside")]);
+    std::fs::create_dir_all(tmp.path().join("outside")).unwrap();
+
+    let assert = cmd().args([&a, &b, "--follow", "--follow-within-root"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "EXTRA-SYMLINK:", "link]"), "got:\n{}", output);
+    assert!(!output.contains("SYMLINK-OUTSIDE-BACKUP"), "got:\n{}", output);
+}
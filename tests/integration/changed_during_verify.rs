@@ -0,0 +1,143 @@
+//! Tests for CHANGED-DURING-VERIFY and --retry-changed.
+
+#[cfg(target_os = "linux")]
+use super::harness::wait_until_open;
+use super::harness::setup_legacy_test_dirs;
+use std::process::{Command, Output};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Run vfy while another thread keeps touching `path`, for at most `touch_for`.
+/// Hashing 256 KiB one byte at a time takes long enough for it to notice.
+fn run_while_touching(args: &[&str], path: &str, touch_for: Duration) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vfy"))
+        .args(args)
+        .args(["--all", "--hash-mode", "read", "--hash-buffer", "1"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let start = Instant::now();
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    let mut tick = 0;
+    while child.try_wait().unwrap().is_none() && start.elapsed() < touch_for {
+        tick += 1;
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(tick)).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    child.wait_with_output().unwrap()
+}
+
+fn setup() -> (tempfile::TempDir, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let data = vec![b'a'; 256 << 10];
+    std::fs::write(format!("{}/live.db", a), &data).unwrap();
+    std::fs::write(format!("{}/live.db", b), &data).unwrap();
+    (tmp, a, b)
+}
+
+#[test]
+fn changing_file_is_reported_and_skipped() {
+    let (_tmp, a, b) = setup();
+    let output = run_while_touching(&[&a, &b], &format!("{}/live.db", a), Duration::from_secs(600));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "got:\n{}", stdout);
+    assert!(
+        stdout.contains(&format!("CHANGED-DURING-VERIFY: [{}/live.db] (original changed)\n", a)),
+        "got:\n{}",
+        stdout
+    );
+    assert!(stdout.contains("    Changed during verify: 1\n"), "got:\n{}", stdout);
+    assert!(stdout.contains("    Skipped: 1\n"), "got:\n{}", stdout);
+    assert!(!stdout.contains("DIFFERENT-FILE"), "got:\n{}", stdout);
+}
+
+#[test]
+fn changing_file_fails_with_fail_on_skipped() {
+    let (_tmp, a, b) = setup();
+    let output = run_while_touching(
+        &[&a, &b, "--fail-on", "skipped"],
+        &format!("{}/live.db", a),
+        Duration::from_secs(600),
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn retry_after_the_file_settles() {
+    let (_tmp, a, b) = setup();
+    let output = run_while_touching(
+        &[&a, &b, "--retry-changed", "1000"],
+        &format!("{}/live.db", a),
+        Duration::from_millis(300),
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "got:\n{}", stdout);
+    assert!(!stdout.contains("CHANGED-DURING-VERIFY"), "got:\n{}", stdout);
+    assert!(stdout.contains("    Similarities: 2\n"), "got:\n{}", stdout);
+}
+
+/// With several backups, a hash of the original cached by an earlier pass
+/// isn't used once the file has changed. Only passes before the last one keep
+/// cached hashes, hence three backups.
+#[cfg(target_os = "linux")]
+#[test]
+fn retry_rehashes_original_cached_by_earlier_pass() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let [c, d] = ["c", "d"].map(|name| tmp.path().join(name).to_str().unwrap().to_string());
+    let big = vec![b'a'; 1 << 20];
+    for (dir, content) in [(&a, "v1"), (&b, "v1"), (&c, "v2"), (&d, "v2")] {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(format!("{}/a.txt", dir), content).unwrap();
+        std::fs::write(format!("{}/z.big", dir), &big).unwrap();
+    }
+
+    let child = Command::new(env!("CARGO_BIN_EXE_vfy"))
+        .args([&a, &b, &c, &d, "--all", "--hash-mode", "read", "--hash-buffer", "1", "--retry-changed", "1"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The first pass is done with a.txt once it hashes z.big. The later
+    // passes find a.txt changed since it was cached, and hash it again.
+    wait_until_open(child.id(), &format!("{}/z.big", a));
+    std::fs::write(format!("{}/a.txt", a), "v2").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "got:\n{}", stdout);
+    assert!(stdout.contains(&format!("SUMMARY for [{}]:", d)), "got:\n{}", stdout);
+    assert!(!stdout.contains("DIFFERENT-FILE"), "got:\n{}", stdout);
+    assert!(!stdout.contains("CHANGED-DURING-VERIFY"), "got:\n{}", stdout);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn original_deleted_while_compared_is_vanished() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let data = vec![b'a'; 1 << 20];
+    std::fs::write(format!("{}/live.db", a), &data).unwrap();
+    std::fs::write(format!("{}/live.db", b), &data).unwrap();
+
+    let child = Command::new(env!("CARGO_BIN_EXE_vfy"))
+        .args([&a, &b, "--all", "--hash-mode", "read", "--hash-buffer", "1"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // An open file can still be read to the end after it's deleted.
+    wait_until_open(child.id(), &format!("{}/live.db", a));
+    std::fs::remove_file(format!("{}/live.db", a)).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "got:\n{}", stdout);
+    assert!(
+        stdout.contains(&format!("VANISHED: [{}/live.db] (deleted while it was compared)\n", a)),
+        "got:\n{}",
+        stdout
+    );
+    assert!(stdout.contains(&format!("SKIP: [{}/live.db]\n", b)), "got:\n{}", stdout);
+    assert!(!stdout.contains("CHANGED-DURING-VERIFY"), "got:\n{}", stdout);
+    assert!(!stdout.contains("ERROR:"), "got:\n{}", stdout);
+    assert!(stdout.contains("    Vanished: 1\n"), "got:\n{}", stdout);
+}
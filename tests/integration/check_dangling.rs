@@ -0,0 +1,129 @@
+//! Tests for --check-dangling, which reports dangling symlinks without
+//! --follow, i.e. without comparing what the symlinks point to.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use crate::case;
+
+// The backup's symlink dangles because its target wasn't copied.
+case!(backup_target_not_copied {
+    orig: [Dir("data"), File("data/f.txt", "x"), Sym("link", "data")],
+    backup: [Sym("link", "data")],
+    flags: ["--check-dangling"],
+    lines: [
+        "MISSING-DIR: a/data",
+        "DANGLING-SYMLINK: b/link",
+        "SYMLINK-SKIPPED: a/link",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["(original's target exists)", "Dangling symlinks: 1"],
+    output_excludes: [],
+    original_processed: 4,
+    backup_processed: 2,
+    missing: 2,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 1,
+    errors: 1,
+    symmetric: false,
+});
+
+// Without the flag, the same backup only has the missing directory.
+case!(backup_target_not_copied_without_flag {
+    orig: [Dir("data"), File("data/f.txt", "x"), Sym("link", "data")],
+    backup: [Sym("link", "data")],
+    flags: [],
+    lines: [
+        "MISSING-DIR: a/data",
+        "SYMLINK-SKIPPED: a/link",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["DANGLING-SYMLINK"],
+    original_processed: 4,
+    backup_processed: 2,
+    missing: 2,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 1,
+    errors: 0,
+    symmetric: false,
+});
+
+// Dangling on both sides: reported for both, without the note.
+case!(both_dangle {
+    orig: [Sym("link", "nowhere")],
+    backup: [Sym("link", "nowhere")],
+    flags: ["--check-dangling"],
+    lines: [
+        "DANGLING-SYMLINK: a/link",
+        "DANGLING-SYMLINK: b/link",
+        "SYMLINK-SKIPPED: a/link",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["Dangling symlinks: 2"],
+    output_excludes: ["(original's target exists)"],
+    original_processed: 2,
+    backup_processed: 2,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 1,
+    errors: 2,
+});
+
+// Extra and missing symlinks are checked too.
+case!(missing_dangling_symlink {
+    orig: [Sym("link", "nowhere")],
+    backup: [],
+    flags: ["--check-dangling"],
+    lines: [
+        "MISSING-SYMLINK: a/link",
+        "DANGLING-SYMLINK: a/link",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: [],
+    original_processed: 2,
+    backup_processed: 1,
+    missing: 1,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 1,
+    skipped: 0,
+    errors: 1,
+});
+
+// Like MISSING- lines, DANGLING-SYMLINK inside a missing directory is only
+// printed with -vv, but always counted.
+#[test]
+fn nested_dangling_symlink_follows_verbosity() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[Dir("sub"), Sym("sub/link", "nowhere")], &[]);
+
+    for verbose in [None, Some("-v")] {
+        let mut args = vec![a.as_str(), b.as_str(), "--check-dangling"];
+        args.extend(verbose);
+        let assert = cmd().args(&args).assert().code(1);
+        let output = stdout_of(&assert);
+        assert!(some_line_has(&output, "MISSING-DIR:", "sub]"), "got:\n{}", output);
+        assert!(!output.contains("MISSING-SYMLINK:"), "got:\n{}", output);
+        assert!(!output.contains("DANGLING-SYMLINK:"), "got:\n{}", output);
+        assert!(output.contains("Dangling symlinks: 1"), "got:\n{}", output);
+    }
+
+    let assert = cmd().args([&a, &b, "--check-dangling", "-vv"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "MISSING-SYMLINK:", "sub/link]"), "got:\n{}", output);
+    assert!(some_line_has(&output, "DANGLING-SYMLINK:", "sub/link]"), "got:\n{}", output);
+}
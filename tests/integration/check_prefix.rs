@@ -0,0 +1,63 @@
+//! Tests for the sizes on DIFFERENT-FILE [SIZE] lines and --check-prefix.
+
+use super::harness::{set_mtime, setup_file_pair};
+use super::{cmd, stdout_of};
+use std::time::{Duration, SystemTime};
+
+fn different_file_line(a: &str, b: &str, extra: &[&str]) -> String {
+    let assert = cmd().args([a, b]).args(extra).assert().code(1);
+    let output = stdout_of(&assert);
+    output
+        .lines()
+        .find(|l| l.starts_with("DIFFERENT-FILE"))
+        .unwrap_or_else(|| panic!("no DIFFERENT-FILE line in:\n{}", output))
+        .to_string()
+}
+
+#[test]
+fn sizes_are_shown() {
+    let (_tmp, a, b) = setup_file_pair("f.txt", b"hello world", b"hello");
+    assert_eq!(
+        different_file_line(&a, &b, &[]),
+        format!("DIFFERENT-FILE [SIZE]: [{}/f.txt] (original 11 bytes, backup 5 bytes)", a)
+    );
+}
+
+#[test]
+fn truncated_backup() {
+    let (_tmp, a, b) = setup_file_pair("f.txt", b"hello world", b"hello");
+    let now = SystemTime::now();
+    set_mtime(&format!("{}/f.txt", a), now - Duration::from_secs(60));
+    set_mtime(&format!("{}/f.txt", b), now);
+    assert!(different_file_line(&a, &b, &["--check-prefix"]).starts_with("DIFFERENT-FILE [SIZE, TRUNCATED]:"));
+}
+
+#[test]
+fn grown_original() {
+    let (_tmp, a, b) = setup_file_pair("f.txt", b"hello world", b"hello");
+    let now = SystemTime::now();
+    set_mtime(&format!("{}/f.txt", a), now);
+    set_mtime(&format!("{}/f.txt", b), now - Duration::from_secs(60));
+    assert!(different_file_line(&a, &b, &["--check-prefix"]).starts_with("DIFFERENT-FILE [SIZE, GROWN]:"));
+}
+
+#[test]
+fn appended_backup() {
+    let (_tmp, a, b) = setup_file_pair("f.txt", b"hello", b"hello world");
+    assert_eq!(
+        different_file_line(&a, &b, &["--check-prefix"]),
+        format!("DIFFERENT-FILE [SIZE, APPENDED]: [{}/f.txt] (original 5 bytes, backup 11 bytes)", a)
+    );
+}
+
+#[test]
+fn real_mismatch() {
+    let (_tmp, a, b) = setup_file_pair("f.txt", b"hello world", b"jello");
+    assert!(different_file_line(&a, &b, &["--check-prefix"]).starts_with("DIFFERENT-FILE [SIZE, MISMATCH]:"));
+}
+
+#[test]
+fn empty_file_is_a_prefix() {
+    let (_tmp, a, b) = setup_file_pair("f.txt", b"", b"data");
+    assert!(different_file_line(&a, &b, &["--check-prefix"]).starts_with("DIFFERENT-FILE [SIZE, APPENDED]:"));
+}
@@ -0,0 +1,126 @@
+//! Tests for --chunk-diff, --manifest and --write-manifest.
+
+use super::harness::{overwrite_keeping_mtime, set_mtime, setup_file_pair};
+use super::{cmd, stdout_of};
+use std::time::{Duration, SystemTime};
+
+fn with_changes(base: &[u8], offsets: &[usize]) -> Vec<u8> {
+    let mut data = base.to_vec();
+    for &i in offsets {
+        data[i] = b'x';
+    }
+    data
+}
+
+#[test]
+fn reports_differing_chunk_ranges() {
+    let orig = vec![b'a'; 3000];
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &with_changes(&orig, &[10, 2500]));
+    let assert = cmd()
+        .args([&a, &b, "--all", "--chunk-diff", "--chunk-size", "1024"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains(&format!(
+            "DIFF-CHUNKS: [{}/f.bin] 2 of 3 chunks of 1024 bytes differ: 0..1024, 2048..3000\n",
+            a
+        )),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn adjacent_chunks_are_merged() {
+    let orig = vec![b'a'; 3000];
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &with_changes(&orig, &[10, 1500]));
+    let assert = cmd()
+        .args([&a, &b, "--all", "--chunk-diff", "--chunk-size", "1024"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("2 of 3 chunks of 1024 bytes differ: 0..2048\n"), "got:\n{}", output);
+}
+
+#[test]
+fn manifest_hashes_replace_reading_the_original() {
+    let orig = vec![b'a'; 3000];
+    let (tmp, a, b) = setup_file_pair("f.bin", &orig, &orig);
+    let manifest = tmp.path().join("manifest.toml");
+    let manifest = manifest.to_str().unwrap();
+
+    cmd()
+        .args([&a, &b, "--all", "--write-manifest", manifest, "--chunk-size", "1024"])
+        .assert()
+        .success();
+
+    // The original now differs from the backup, but the manifest says it's
+    // unchanged, so its stored hash is used.
+    let orig_file = format!("{}/f.bin", a);
+    overwrite_keeping_mtime(&orig_file, &with_changes(&orig, &[10]));
+    cmd().args([&a, &b, "--all"]).assert().code(1);
+    cmd().args([&a, &b, "--all", "--manifest", manifest]).assert().success();
+
+    // Stored chunks are used for --chunk-diff too: only the backup's change
+    // shows up.
+    std::fs::write(format!("{}/f.bin", b), with_changes(&orig, &[2500])).unwrap();
+    let assert = cmd()
+        .args([&a, &b, "--all", "--manifest", manifest, "--chunk-diff", "--chunk-size", "1024"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("1 of 3 chunks of 1024 bytes differ: 2048..3000\n"), "got:\n{}", output);
+}
+
+#[test]
+fn manifest_entry_of_changed_file_is_ignored() {
+    let orig = vec![b'a'; 3000];
+    let (tmp, a, b) = setup_file_pair("f.bin", &orig, &orig);
+    let manifest = tmp.path().join("manifest.toml");
+    let manifest = manifest.to_str().unwrap();
+
+    cmd().args([&a, &b, "--all", "--write-manifest", manifest]).assert().success();
+
+    let orig_file = format!("{}/f.bin", a);
+    std::fs::write(&orig_file, with_changes(&orig, &[10])).unwrap();
+    let later = SystemTime::now() + Duration::from_secs(10);
+    set_mtime(&orig_file, later);
+
+    cmd().args([&a, &b, "--all", "--manifest", manifest]).assert().code(1);
+}
+
+#[test]
+fn manifest_entries_carry_over_to_the_new_manifest() {
+    let orig = vec![b'a'; 3000];
+    let (tmp, a, b) = setup_file_pair("f.bin", &orig, &orig);
+    std::fs::write(format!("{}/g.bin", a), b"abc").unwrap();
+    std::fs::write(format!("{}/g.bin", b), b"abc").unwrap();
+    let m1 = tmp.path().join("m1.toml");
+    let m2 = tmp.path().join("m2.toml");
+    let (m1, m2) = (m1.to_str().unwrap(), m2.to_str().unwrap());
+
+    cmd().args([&a, &b, "--all", "--write-manifest", m1]).assert().success();
+    cmd()
+        .args([&a, &b, "--all", "--manifest", m1, "--write-manifest", m2])
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(m1).unwrap(), std::fs::read_to_string(m2).unwrap());
+
+    // The entries in m2 are still used after the original is corrupted.
+    overwrite_keeping_mtime(&format!("{}/f.bin", a), &with_changes(&orig, &[10]));
+    cmd().args([&a, &b, "--all", "--manifest", m2]).assert().success();
+}
+
+#[test]
+fn manifest_errors_exit_2() {
+    let (tmp, a, b) = setup_file_pair("f.bin", b"abc", b"abc");
+    cmd().args([&a, &b, "--write-manifest", "m.toml"]).assert().code(2);
+
+    let missing = tmp.path().join("missing.toml");
+    cmd().args([&a, &b, "--all", "--manifest", missing.to_str().unwrap()]).assert().code(2);
+
+    let bad = tmp.path().join("bad.toml");
+    std::fs::write(&bad, "chunk_size = 0\n").unwrap();
+    cmd().args([&a, &b, "--all", "--manifest", bad.to_str().unwrap()]).assert().code(2);
+}
@@ -39,7 +39,7 @@ fn require_dev_shm_different_fs() {
 /// to delete the target tree.
 #[cfg(unix)]
 fn clean_shm_test_dir(path: &std::path::Path) {
-    if std::fs::symlink_metadata(path).map_or(false, |m| m.is_dir()) {
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
         let _ = std::fs::remove_dir_all(path);
     }
 }
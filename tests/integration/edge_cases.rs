@@ -380,6 +380,10 @@ case!(special_files_missing_from_backup {
         "MISSING-SYMLINK: a/sub/devnull",
         "SPECIAL-FILE: a/sub/devnull",
     ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: [],
     // root + sub + ok.txt + devnull symlink (2 with --follow) = 5
     original_processed: 5,
     backup_processed: 3,
@@ -392,6 +396,8 @@ case!(special_files_missing_from_backup {
     similarities: 3,
     skipped: 0,
     errors: 0,
+    // Not symmetric: only backup symlinks are checked for leaving the backup.
+    symmetric: false,
 });
 
 // Symlink to /dev/null extra in backup with --follow
@@ -410,8 +416,13 @@ case!(special_files_extra_in_backup {
     flags: ["--follow"],
     lines: [
         "EXTRA-SYMLINK: b/sub/devnull",
+        "SYMLINK-OUTSIDE-BACKUP: b/sub/devnull",
         "SPECIAL-FILE: b/sub/devnull",
     ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["Backup symlinks outside the backup: 1"],
+    output_excludes: [],
     original_processed: 3,
     // root + sub + ok.txt + devnull symlink (2 with --follow) = 5
     backup_processed: 5,
@@ -424,6 +435,8 @@ case!(special_files_extra_in_backup {
     similarities: 3,
     skipped: 0,
     errors: 0,
+    // Not symmetric: only backup symlinks are checked for leaving the backup.
+    symmetric: false,
 });
 
 // ===========================================================================
@@ -479,10 +492,11 @@ fn symlink_to_dev_dir_with_follow() {
 #[test]
 fn same_directory_warning() {
     let (_tmp, a, _b) = setup_legacy_test_dirs(IDENTICAL, IDENTICAL);
+    // Every file is NOT-INDEPENDENT, which fails the run.
     cmd()
         .args([&a, &a])
         .assert()
-        .success()
+        .code(1)
         .stderr(predicate::str::contains("same directory"));
 }
 
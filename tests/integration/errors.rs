@@ -344,7 +344,7 @@ fn nonexistent_original_exits_2() {
 }
 
 #[test]
-fn original_is_file_not_dir() {
+fn original_is_file_backup_is_dir() {
     // Use an existing file as the "original" argument
     let file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("Cargo.toml")
@@ -356,8 +356,19 @@ fn original_is_file_not_dir() {
     cmd()
         .args([&file_path, &b])
         .assert()
+        .code(1)
+        .stdout(predicate::str::contains("FILE-DIR-MISMATCH:"));
+}
+
+#[cfg(unix)]
+#[test]
+fn special_file_root_exits_2() {
+    let (_tmp, a, _b) = setup_legacy_test_dirs(&[], &[]);
+    cmd()
+        .args([&a, "/dev/null"])
+        .assert()
         .code(2)
-        .stderr(predicate::str::contains("is not a directory"));
+        .stderr(predicate::str::contains("is not a directory or file"));
 }
 
 #[test]
@@ -371,7 +382,7 @@ fn nonexistent_backup_exits_2() {
 }
 
 #[test]
-fn backup_is_file_not_dir() {
+fn original_is_dir_backup_is_file() {
     let (_tmp, a, _b) = setup_legacy_test_dirs(&[], &[]);
     let file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("Cargo.toml")
@@ -382,8 +393,8 @@ fn backup_is_file_not_dir() {
     cmd()
         .args([&a, &file_path])
         .assert()
-        .code(2)
-        .stderr(predicate::str::contains("is not a directory"));
+        .code(1)
+        .stdout(predicate::str::contains("FILE-DIR-MISMATCH:"));
 }
 
 // ===========================================================================
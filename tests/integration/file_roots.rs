@@ -0,0 +1,81 @@
+//! Tests for comparing two files given directly as the roots.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use predicates::prelude::*;
+
+/// Paths of the entries `a/name` and `b/name`.
+fn roots(a: &str, b: &str, name: &str) -> (String, String) {
+    (format!("{}/{}", a, name), format!("{}/{}", b, name))
+}
+
+#[test]
+fn identical_files_exit_0() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("db.dump", "data")], &[File("db.dump", "data")]);
+    let (orig, backup) = roots(&a, &b, "db.dump");
+    let assert = cmd().args([&orig, &backup, "--all"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("Original items processed: 1"), "got:\n{}", output);
+    assert!(output.contains("Similarities: 1"), "got:\n{}", output);
+}
+
+#[test]
+fn size_difference() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("db.dump", "data")], &[File("db.dump", "dat")]);
+    let (orig, backup) = roots(&a, &b, "db.dump");
+    let assert = cmd().args([&orig, &backup]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [SIZE]:", "db.dump"), "got:\n{}", output);
+}
+
+#[test]
+fn same_size_needs_hash() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("db.dump", "aaaa")], &[File("db.dump", "abba")]);
+    let (orig, backup) = roots(&a, &b, "db.dump");
+    cmd().args([&orig, &backup]).assert().success();
+    let assert = cmd().args([&orig, &backup, "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [HASH]:", "db.dump"), "got:\n{}", output);
+}
+
+#[test]
+fn file_vs_dir_is_mismatch() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("x", "data")], &[Dir("x")]);
+    let (orig, backup) = roots(&a, &b, "x");
+    let assert = cmd().args([&orig, &backup]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "FILE-DIR-MISMATCH:", "x]"), "got:\n{}", output);
+}
+
+#[test]
+fn symlink_root_needs_follow() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("db.dump", "data"), Sym("link", "db.dump")],
+        &[File("db.dump", "data")],
+    );
+    let (link, _) = roots(&a, &b, "link");
+    let (_, backup) = roots(&a, &b, "db.dump");
+    cmd()
+        .args([&link, &backup])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("is a symlink to a file, use --follow"));
+    cmd().args([&link, &backup, "--follow", "--all"]).assert().success();
+}
+
+#[test]
+fn previous_file() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("db.dump", "aaaa"), File("prev.dump", "aaaa")],
+        &[File("db.dump", "aaaaaa")],
+    );
+    let (orig, backup) = roots(&a, &b, "db.dump");
+    let (previous, _) = roots(&a, &b, "prev.dump");
+    let assert = cmd().args([&orig, &backup, "--previous", &previous]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-FILE [SIZE]:", "(corrupted in backup)"),
+        "got:\n{}",
+        output
+    );
+}
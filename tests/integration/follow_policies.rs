@@ -0,0 +1,123 @@
+//! Tests for the --follow policies: --follow-within-root, --follow-files-only
+//! and --max-follow-depth.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use crate::case;
+use predicates::prelude::*;
+
+case!(files_only_skips_dir_symlinks {
+    orig: [
+        Dir("data"),
+        File("data/f.txt", "x"),
+        Sym("dirlink", "data"),
+        Sym("filelink", "data/f.txt"),
+    ],
+    backup: [
+        Dir("data"),
+        File("data/f.txt", "x"),
+        Sym("dirlink", "data"),
+        Sym("filelink", "data/f.txt"),
+    ],
+    flags: ["--follow", "--follow-files-only"],
+    lines: [
+        "SYMLINK-SKIPPED: a/dirlink",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["(not followed: target is a directory)"],
+    output_excludes: [],
+    // root + data + data/f.txt + dirlink + filelink + resolved filelink
+    original_processed: 6,
+    backup_processed: 6,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 6,
+    skipped: 1,
+    errors: 0,
+});
+
+case!(max_follow_depth_stops_nested_symlinks {
+    orig: [
+        Dir("data"),
+        Dir("data/inner"),
+        File("data/inner/f.txt", "x"),
+        Sym("data/innerlink", "inner"),
+        Sym("link", "data"),
+    ],
+    backup: [
+        Dir("data"),
+        Dir("data/inner"),
+        File("data/inner/f.txt", "x"),
+        Sym("data/innerlink", "inner"),
+        Sym("link", "data"),
+    ],
+    flags: ["--follow", "--max-follow-depth", "1"],
+    lines: [
+        "SYMLINK-SKIPPED: a/link/innerlink",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["(not followed: --max-follow-depth 1 reached)"],
+    output_excludes: [],
+    // root + data + data/inner + data/inner/f.txt + data/innerlink + resolved data/innerlink
+    // + data/innerlink/f.txt + link + resolved link + link/inner + link/inner/f.txt + link/innerlink
+    original_processed: 12,
+    backup_processed: 12,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 12,
+    skipped: 1,
+    errors: 0,
+});
+
+#[test]
+fn within_root_skips_symlinks_leaving_the_root() {
+    let entries = &[Sym("outside", "../shared"), Dir("data"), Sym("inside", "data")];
+    let (tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    std::fs::create_dir_all(tmp.path().join("shared")).unwrap();
+    std::fs::write(tmp.path().join("shared/huge.bin"), "x").unwrap();
+
+    let assert = cmd().args([&a, &b, "--follow", "--follow-within-root"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "SYMLINK-SKIPPED:", "outside] (not followed: target is outside the original)"),
+        "got:\n{}",
+        output
+    );
+    assert!(!some_line_has(&output, "SYMLINK-SKIPPED:", "inside]"), "got:\n{}", output);
+    assert!(!output.contains("huge.bin"), "got:\n{}", output);
+}
+
+#[test]
+fn within_root_checks_the_backup_side() {
+    // The backup's symlink points back into the original, which isn't within the backup.
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("data"), Sym("link", "data")],
+        &[Dir("data"), Sym("link", "../a/data")],
+    );
+    let assert = cmd().args([&a, &b, "--follow", "--follow-within-root"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "SYMLINK-SKIPPED:", "(not followed: target is outside the backup)"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn policies_require_follow() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    for flags in [&["--follow-within-root"][..], &["--follow-files-only"], &["--max-follow-depth", "1"]] {
+        cmd()
+            .args([&a, &b])
+            .args(flags)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("--follow"));
+    }
+}
@@ -456,8 +456,8 @@ pub fn is_diagnostic_line(line: &str) -> bool {
     if line.starts_with("SUMMARY:") || line.starts_with("    ") {
         return false;
     }
-    // CMD line at the top
-    if line.starts_with("CMD:") {
+    // CMD and SAMPLING lines at the top
+    if line.starts_with("CMD:") || line.starts_with("SAMPLING:") {
         return false;
     }
     if line.starts_with("COMPARISON FINISHED!") {
@@ -471,6 +471,7 @@ pub fn is_diagnostic_line(line: &str) -> bool {
 }
 
 /// Run vfy and check output lines, summary counts, DEBUG lines, and output substrings.
+#[allow(clippy::too_many_arguments)]
 pub fn run_and_check_full(
     label: &str,
     tmp: &Path,
@@ -658,6 +659,7 @@ pub fn check(
 }
 
 /// Entry point for case! macro with DEBUG checking: runs forward and reversed tests.
+#[allow(clippy::too_many_arguments)]
 pub fn check_with_debug(
     name: &str,
     orig_entries: &[Entry],
@@ -682,6 +684,7 @@ pub fn check_with_debug(
 }
 
 /// Entry point for case! macro with all checking options: runs forward and reversed tests.
+#[allow(clippy::too_many_arguments)]
 pub fn check_full(
     name: &str,
     orig_entries: &[Entry],
@@ -727,6 +730,50 @@ pub fn setup_legacy_test_dirs(
     )
 }
 
+/// Create test directories with the file `name` (which may be in a
+/// subdirectory) holding `orig` in the original and `backup` in the backup.
+/// Returns (TempDir, orig_path_string, backup_path_string).
+pub fn setup_file_pair(name: &str, orig: &[u8], backup: &[u8]) -> (tempfile::TempDir, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    for (dir, content) in [(&a, orig), (&b, backup)] {
+        let path = Path::new(dir).join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    (tmp, a, b)
+}
+
+/// Set the modification time of `path`.
+pub fn set_mtime(path: &str, mtime: std::time::SystemTime) {
+    std::fs::File::options().write(true).open(path).unwrap().set_modified(mtime).unwrap();
+}
+
+/// Overwrite `path` without changing its size or modification time, as if
+/// it had been corrupted behind vfy's back.
+pub fn overwrite_keeping_mtime(path: &str, content: &[u8]) {
+    let mtime = std::fs::metadata(path).unwrap().modified().unwrap();
+    std::fs::write(path, content).unwrap();
+    set_mtime(path, mtime);
+}
+
+/// Wait until the process `pid` has `path` open, e.g. to change a file while
+/// vfy is in the middle of reading it. Linux only, since it looks at /proc.
+#[cfg(target_os = "linux")]
+pub fn wait_until_open(pid: u32, path: &str) {
+    let path = Path::new(path).canonicalize().unwrap();
+    let start = std::time::Instant::now();
+    loop {
+        let fds = std::fs::read_dir(format!("/proc/{}/fd", pid))
+            .unwrap_or_else(|e| panic!("vfy exited before opening {:?}: {}", path, e));
+        if fds.flatten().any(|fd| std::fs::read_link(fd.path()).is_ok_and(|p| p == path)) {
+            return;
+        }
+        assert!(start.elapsed() < std::time::Duration::from_secs(60), "vfy never opened {:?}", path);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn check_internal(
     name: &str,
     orig_entries: &[Entry],
@@ -0,0 +1,92 @@
+//! Tests for --hash-mode, --hash-buffer and --hash-threads.
+
+use super::harness::setup_legacy_test_dirs;
+use super::{cmd, stdout_of};
+
+/// Files of several sizes, the same on both sides except `changed.bin`.
+fn setup() -> (tempfile::TempDir, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let big: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+    for dir in [&a, &b] {
+        std::fs::write(format!("{}/empty.bin", dir), b"").unwrap();
+        std::fs::write(format!("{}/small.bin", dir), b"hello").unwrap();
+        std::fs::write(format!("{}/big.bin", dir), &big).unwrap();
+    }
+    let mut changed = big.clone();
+    std::fs::write(format!("{}/changed.bin", a), &changed).unwrap();
+    changed[250_000] ^= 1;
+    std::fs::write(format!("{}/changed.bin", b), &changed).unwrap();
+    (tmp, a, b)
+}
+
+fn hash_lines(a: &str, b: &str, extra: &[&str]) -> Vec<String> {
+    let assert = cmd().args([a, b, "--all", "-vv"]).args(extra).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("DIFFERENT-FILE [HASH]:"), "got:\n{}", output);
+    let mut lines: Vec<String> =
+        output.lines().filter(|l| l.starts_with("DEBUG: BLAKE3 ")).map(String::from).collect();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn all_modes_give_the_same_hashes() {
+    let (_tmp, a, b) = setup();
+    let expected = hash_lines(&a, &b, &[]);
+    assert_eq!(expected.len(), 8);
+
+    for extra in [
+        &["--hash-mode", "auto"][..],
+        &["--hash-mode", "mmap"][..],
+        &["--hash-mode", "read"][..],
+        &["--hash-mode", "read", "--hash-buffer", "1000"][..],
+        &["--hash-mode", "read", "--hash-buffer", "1"][..],
+        &["--hash-mode", "read", "--hash-threads", "1"][..],
+        &["--hash-mode", "mmap", "--hash-threads", "2"][..],
+    ] {
+        assert_eq!(hash_lines(&a, &b, extra), expected, "with {:?}", extra);
+    }
+}
+
+#[test]
+fn invalid_values_exit_2() {
+    let (_tmp, a, b) = setup();
+    cmd().args([&a, &b, "--all", "--hash-mode", "stream"]).assert().code(2);
+    cmd().args([&a, &b, "--all", "--hash-buffer", "0"]).assert().code(2);
+    cmd().args([&a, &b, "--all", "--hash-threads", "0"]).assert().code(2);
+}
+
+/// The default mode never maps files, so a file truncated while it's hashed
+/// can't kill vfy with SIGBUS.
+#[cfg(target_os = "linux")]
+#[test]
+fn default_mode_survives_truncation() {
+    use super::harness::wait_until_open;
+
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let big = vec![b'a'; 2 << 20];
+    std::fs::write(format!("{}/a.big", a), &big).unwrap();
+    std::fs::write(format!("{}/a.big", b), &big).unwrap();
+
+    // One byte at a time takes long enough to look at it while it's hashing.
+    let child = std::process::Command::new(env!("CARGO_BIN_EXE_vfy"))
+        .args([&a, &b, "--all", "--hash-buffer", "1"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    wait_until_open(child.id(), &format!("{}/a.big", b));
+    let maps = std::fs::read_to_string(format!("/proc/{}/maps", child.id())).unwrap();
+    assert!(!maps.contains("a.big"), "mapped:\n{}", maps);
+    std::fs::File::options()
+        .write(true)
+        .open(format!("{}/a.big", b))
+        .unwrap()
+        .set_len(1000)
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // Exits normally, and notices the change instead.
+    assert_eq!(output.status.code(), Some(0), "got:\n{}", stdout);
+    assert!(stdout.contains("CHANGED-DURING-VERIFY:"), "got:\n{}", stdout);
+}
@@ -0,0 +1,73 @@
+//! Tests for --locate-diff and --locate-limit.
+
+use super::harness::setup_file_pair;
+use super::{cmd, stdout_of};
+
+/// 3 MiB of `a`, with bytes 5000..5010 and the last 3 bytes changed.
+fn corrupted() -> (Vec<u8>, Vec<u8>) {
+    let orig = vec![b'a'; 3 << 20];
+    let mut backup = orig.clone();
+    backup[5000..5010].fill(b'b');
+    let len = backup.len();
+    backup[len - 3..].fill(b'c');
+    (orig, backup)
+}
+
+#[test]
+fn reports_first_offset_and_ranges() {
+    let (orig, backup) = corrupted();
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    let assert = cmd().args([&a, &b, "--all", "--locate-diff"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains(&format!(
+            "DIFF-LOCATION: [{}/f.bin] first difference at byte 5000, 2 differing ranges (13 bytes)\n",
+            a
+        )),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn limit_bounds_the_search() {
+    let (orig, backup) = corrupted();
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    let assert = cmd()
+        .args([&a, &b, "--all", "--locate-diff", "--locate-limit", "6000"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains("first difference at byte 5000, 1 differing ranges (10 bytes) in the first 6000 bytes"),
+        "got:\n{}",
+        output
+    );
+
+    let assert = cmd()
+        .args([&a, &b, "--all", "--locate-diff", "--locate-limit", "4096"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("no difference found in the first 4096 bytes"), "got:\n{}", output);
+}
+
+#[test]
+fn not_printed_for_size_differences_or_without_option() {
+    let (_tmp, a, b) = setup_file_pair("f.bin", b"abc", b"abcd");
+    let assert = cmd().args([&a, &b, "--all", "--locate-diff"]).assert().code(1);
+    assert!(!stdout_of(&assert).contains("DIFF-LOCATION:"));
+
+    let (orig, backup) = corrupted();
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+    let assert = cmd().args([&a, &b, "--all"]).assert().code(1);
+    assert!(!stdout_of(&assert).contains("DIFF-LOCATION:"));
+}
+
+#[test]
+fn locate_limit_requires_locate_diff() {
+    let (_tmp, a, b) = setup_file_pair("f.bin", b"abc", b"abc");
+    cmd().args([&a, &b, "--locate-limit", "10"]).assert().code(2);
+}
@@ -1,15 +1,37 @@
+mod allow_growth;
+mod backup_symlinks;
 mod basic;
+mod changed_during_verify;
+mod check_dangling;
+mod check_prefix;
+mod chunk_diff;
 mod different_fs;
 mod edge_cases;
 mod errors;
+mod file_roots;
 mod flags;
+mod follow_policies;
 mod harness;
+mod hash_mode;
 mod inside_missing_extra;
+mod locate_diff;
+mod map;
 mod matrix;
+mod multi_backup;
+mod normalize_symlinks;
+mod not_independent;
+mod policy;
+mod previous;
+mod profiles;
 mod release_critical;
+mod sampling;
+mod superset;
 mod symlink_loops;
 mod symlinks;
 mod symlinks_nested;
+mod thresholds;
+mod vanished;
+mod zeroed;
 
 use assert_cmd::Command;
 
@@ -0,0 +1,135 @@
+//! Tests for --map, which compares a subtree of the original against a
+//! differently named subtree of the backup.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use predicates::prelude::*;
+
+#[test]
+fn renamed_subtree_is_compared() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("docs"), File("docs/f.txt", "aaaa")],
+        &[Dir("documents"), File("documents/f.txt", "abba")],
+    );
+    let assert = cmd().args([&a, &b, "--all", "--map", "docs=documents"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-FILE [HASH]:", "docs/f.txt"),
+        "got:\n{}",
+        output
+    );
+    assert!(!output.contains("MISSING-"), "got:\n{}", output);
+    assert!(!output.contains("EXTRA-"), "got:\n{}", output);
+}
+
+#[test]
+fn identical_renamed_subtree_exits_0() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("docs"), File("docs/f.txt", "x"), File("same.txt", "s")],
+        &[Dir("documents"), File("documents/f.txt", "x"), File("same.txt", "s")],
+    );
+    let assert = cmd().args([&a, &b, "--all", "--map", "docs=documents"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("Similarities: 4"), "got:\n{}", output);
+}
+
+#[test]
+fn nested_rules() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("home"), Dir("home/alice"), Dir("home/alice/docs"), File("home/alice/docs/f.txt", "x")],
+        &[Dir("users"), Dir("users/alice"), Dir("users/alice/documents"), File("users/alice/documents/f.txt", "x")],
+    );
+    cmd()
+        .args([&a, &b, "--map", "home=users", "--map", "home/alice/docs=users/alice/documents"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn parents_of_mapped_paths_are_expected() {
+    // The example from --help: neither home nor users exists on the other side.
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("home"), Dir("home/alice"), Dir("home/alice/docs"), File("home/alice/docs/f.txt", "x")],
+        &[Dir("users"), Dir("users/alice"), Dir("users/alice/documents"), File("users/alice/documents/f.txt", "x")],
+    );
+    let assert = cmd()
+        .args([&a, &b, "--all", "--map", "home/alice/docs=users/alice/documents"])
+        .assert()
+        .success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("MISSING-"), "got:\n{}", output);
+    assert!(!output.contains("EXTRA-"), "got:\n{}", output);
+}
+
+#[test]
+fn parents_of_mapped_paths_at_different_depths() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[
+            Dir("home"),
+            Dir("home/alice"),
+            Dir("home/alice/docs"),
+            File("home/alice/docs/f.txt", "aaaa"),
+            Dir("home/bob"),
+        ],
+        &[
+            Dir("archive"),
+            Dir("archive/2024"),
+            Dir("archive/2024/alice"),
+            Dir("archive/2024/alice/documents"),
+            File("archive/2024/alice/documents/f.txt", "abba"),
+            File("archive/2024/stray.txt", "s"),
+        ],
+    );
+    let assert = cmd()
+        .args([&a, &b, "--all", "--map", "home/alice/docs=archive/2024/alice/documents"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [HASH]:", "docs/f.txt"), "got:\n{}", output);
+    // Only the entries that don't lead to the mapped paths are reported.
+    assert!(some_line_has(&output, "MISSING-DIR:", "home/bob]"), "got:\n{}", output);
+    assert!(some_line_has(&output, "EXTRA-FILE:", "2024/stray.txt]"), "got:\n{}", output);
+    assert_eq!(output.lines().filter(|l| l.starts_with("MISSING-") || l.starts_with("EXTRA-")).count(), 2, "got:\n{}", output);
+}
+
+#[test]
+fn unmapped_names_are_missing_and_extra() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("docs"), File("docs/f.txt", "x"), File("only_orig.txt", "o")],
+        &[Dir("documents"), File("documents/f.txt", "x"), Dir("docs"), File("only_backup.txt", "b")],
+    );
+    let assert = cmd().args([&a, &b, "--map", "docs=documents"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "EXTRA-DIR:", "docs]"), "got:\n{}", output);
+    assert!(some_line_has(&output, "EXTRA-FILE:", "only_backup.txt"), "got:\n{}", output);
+    assert!(some_line_has(&output, "MISSING-FILE:", "only_orig.txt"), "got:\n{}", output);
+    assert!(!output.contains("documents]"), "got:\n{}", output);
+}
+
+#[test]
+fn missing_mapped_target_is_missing() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[Dir("docs"), File("docs/f.txt", "x")], &[]);
+    let assert = cmd().args([&a, &b, "--map", "docs=documents"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "MISSING-DIR:", "docs]"), "got:\n{}", output);
+}
+
+#[test]
+fn mapped_target_without_original_is_extra() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[Dir("documents")]);
+    let assert = cmd().args([&a, &b, "--map", "docs=documents"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "EXTRA-DIR:", "documents]"), "got:\n{}", output);
+}
+
+#[test]
+fn invalid_rules_exit_2() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    for rule in ["docs", "=documents", "docs=", "/abs=documents", "docs=../up"] {
+        cmd()
+            .args([&a, &b, "--map", rule])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("--map expects ORIG_SUBPATH=BACKUP_SUBPATH"));
+    }
+}
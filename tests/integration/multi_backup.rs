@@ -0,0 +1,156 @@
+//! Tests for verifying one original against several backups in a single run.
+
+use super::harness::{create_entries, setup_legacy_test_dirs, Entry, Entry::*};
+use super::{cmd, stdout_of};
+use predicates::prelude::*;
+
+const ORIG: &[Entry] = &[
+    File("hello.txt", "hello world\n"),
+    Dir("sub"),
+    File("sub/nested.txt", "nested file\n"),
+];
+
+const CORRUPTED: &[Entry] = &[
+    File("hello.txt", "HELLO WORLD\n"),
+    Dir("sub"),
+];
+
+/// Create the original as `a` and one more backup `c` next to `b`.
+fn setup_three(
+    orig: &[Entry],
+    backup1: &[Entry],
+    backup2: &[Entry],
+) -> (tempfile::TempDir, String, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(orig, backup1);
+    let c = tmp.path().join("c");
+    std::fs::create_dir_all(&c).unwrap();
+    create_entries(&c, backup2);
+    let c = c.to_str().unwrap().to_string();
+    (tmp, a, b, c)
+}
+
+/// Return the lines between the "BACKUP n of m" header for `backup` and the next summary.
+fn section<'a>(output: &'a str, backup: &str) -> Vec<&'a str> {
+    output
+        .lines()
+        .skip_while(|l| !(l.starts_with("BACKUP ") && l.contains(backup)))
+        .skip(1)
+        .take_while(|l| !l.starts_with("SUMMARY"))
+        .collect()
+}
+
+#[test]
+fn two_identical_backups_exit_0() {
+    let (_tmp, a, b, c) = setup_three(ORIG, ORIG, ORIG);
+    let assert = cmd().args([&a, &b, &c]).assert().success();
+    let output = stdout_of(&assert);
+
+    assert!(output.contains(&format!("BACKUP 1 of 2: [{}]", b)), "got:\n{}", output);
+    assert!(output.contains(&format!("BACKUP 2 of 2: [{}]", c)), "got:\n{}", output);
+    assert!(output.contains(&format!("SUMMARY for [{}]:", b)), "got:\n{}", output);
+    assert!(output.contains(&format!("SUMMARY for [{}]:", c)), "got:\n{}", output);
+    assert!(output.contains("SUMMARY (all backups):"), "got:\n{}", output);
+    // Combined counts are totals over both passes.
+    assert!(output.contains("Original items processed: 8"), "got:\n{}", output);
+}
+
+#[test]
+fn findings_reported_per_backup() {
+    let (_tmp, a, b, c) = setup_three(ORIG, ORIG, CORRUPTED);
+    let assert = cmd().args([&a, &b, &c, "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+
+    let first = section(&output, &b);
+    assert!(first.is_empty(), "first backup is fine, got: {:?}", first);
+
+    let second = section(&output, &c);
+    assert!(
+        second.iter().any(|l| l.starts_with("DIFFERENT-FILE [HASH]:") && l.contains("hello.txt")),
+        "got: {:?}",
+        second
+    );
+    assert!(
+        second.iter().any(|l| l.starts_with("MISSING-FILE:") && l.contains("nested.txt")),
+        "got: {:?}",
+        second
+    );
+}
+
+#[test]
+fn every_pass_sees_the_cached_original() {
+    // Backups after the first get the original from the cache, and the last
+    // one takes it out of the cache. Each must still be checked fully.
+    let (tmp, a, b, c) = setup_three(ORIG, ORIG, ORIG);
+    let d = tmp.path().join("d");
+    std::fs::create_dir_all(&d).unwrap();
+    create_entries(&d, CORRUPTED);
+    let d = d.to_str().unwrap().to_string();
+
+    let assert = cmd().args([&a, &b, &c, &d, "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+
+    assert!(section(&output, &b).is_empty(), "got:\n{}", output);
+    assert!(section(&output, &c).is_empty(), "got:\n{}", output);
+    let last = section(&output, &d);
+    assert!(
+        last.iter().any(|l| l.starts_with("DIFFERENT-FILE [HASH]:") && l.contains("hello.txt")),
+        "got: {:?}",
+        last
+    );
+    assert!(output.contains("Original items processed: 12"), "got:\n{}", output);
+}
+
+#[test]
+fn per_backup_and_combined_summaries() {
+    let (_tmp, a, b, c) = setup_three(ORIG, ORIG, CORRUPTED);
+    let assert = cmd().args([&a, &b, &c]).assert().code(1);
+    let output = stdout_of(&assert);
+
+    let summary_for = |heading: &str| -> String {
+        output
+            .lines()
+            .skip_while(|l| *l != heading)
+            .skip(1)
+            .take_while(|l| l.starts_with("    "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let first = summary_for(&format!("SUMMARY for [{}]:", b));
+    assert!(first.contains("Missing: 0"), "got:\n{}", first);
+    let second = summary_for(&format!("SUMMARY for [{}]:", c));
+    assert!(second.contains("Missing: 1"), "got:\n{}", second);
+    let combined = summary_for("SUMMARY (all backups):");
+    assert!(combined.contains("Missing: 1"), "got:\n{}", combined);
+    assert!(combined.contains("Original items processed: 8"), "got:\n{}", combined);
+}
+
+#[test]
+fn single_backup_output_unchanged() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(ORIG, ORIG);
+    let assert = cmd().args([&a, &b]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("BACKUP "), "got:\n{}", output);
+    assert!(output.lines().any(|l| l == "SUMMARY:"), "got:\n{}", output);
+}
+
+#[test]
+fn ignore_path_in_second_backup() {
+    let (_tmp, a, b, c) = setup_three(ORIG, ORIG, CORRUPTED);
+    let ignore = format!("{}/sub", c);
+    // Only the ignored subtree differs in size-only mode, so this passes.
+    let assert = cmd().args([&a, &b, &c, "-i", &ignore]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("MISSING-FILE:"), "got:\n{}", output);
+    assert!(output.contains(&format!("SKIP: [{}/sub]", c)), "got:\n{}", output);
+}
+
+#[test]
+fn nonexistent_second_backup_exits_2() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(ORIG, ORIG);
+    cmd()
+        .args([&a, &b, "/nonexistent/dir/backup"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Cannot resolve backup directory"));
+}
@@ -0,0 +1,116 @@
+//! Tests for --normalize-symlinks, which compares symlink targets by where
+//! they point in each tree instead of byte-for-byte.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use crate::case;
+
+case!(dot_prefix_is_equivalent {
+    orig: [Dir("lib"), File("lib/x", "x"), Dir("bin"), Sym("bin/x", "../lib/x")],
+    backup: [Dir("lib"), File("lib/x", "x"), Dir("bin"), Sym("bin/x", "./../lib/x")],
+    flags: ["--normalize-symlinks"],
+    lines: ["SYMLINK-SKIPPED: a/bin/x"],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["DIFFERENT-SYMLINK-TARGET"],
+    original_processed: 5,
+    backup_processed: 5,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 5,
+    skipped: 1,
+    errors: 0,
+});
+
+case!(dot_prefix_differs_without_flag {
+    orig: [Dir("lib"), File("lib/x", "x"), Dir("bin"), Sym("bin/x", "../lib/x")],
+    backup: [Dir("lib"), File("lib/x", "x"), Dir("bin"), Sym("bin/x", "./../lib/x")],
+    flags: [],
+    lines: [
+        "DIFFERENT-SYMLINK-TARGET: a/bin/x",
+        "SYMLINK-SKIPPED: a/bin/x",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["normalized:"],
+    original_processed: 5,
+    backup_processed: 5,
+    missing: 0,
+    different: 1,
+    extras: 0,
+    special_files: 0,
+    similarities: 4,
+    skipped: 1,
+    errors: 0,
+});
+
+case!(different_place_shows_normalized_targets {
+    orig: [Dir("lib"), File("lib/x", "x"), File("lib/y", "y"), Sym("link", "lib/x")],
+    backup: [Dir("lib"), File("lib/x", "x"), File("lib/y", "y"), Sym("link", "./lib/y")],
+    flags: ["--normalize-symlinks"],
+    lines: [
+        "DIFFERENT-SYMLINK-TARGET: a/link",
+        "SYMLINK-SKIPPED: a/link",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["normalized: \"lib/x\" vs \"lib/y\""],
+    output_excludes: [],
+    original_processed: 5,
+    backup_processed: 5,
+    missing: 0,
+    different: 1,
+    extras: 0,
+    special_files: 0,
+    similarities: 4,
+    skipped: 1,
+    errors: 0,
+    symmetric: false,
+});
+
+#[test]
+fn absolute_target_in_original_is_rebased() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("lib"), File("lib/x", "x")],
+        &[Dir("lib"), File("lib/x", "x")],
+    );
+    // The original's symlink is absolute, the backup's was rewritten to be relative.
+    std::os::unix::fs::symlink(format!("{}/lib/x", a), format!("{}/link", a)).unwrap();
+    std::os::unix::fs::symlink("lib/x", format!("{}/link", b)).unwrap();
+
+    cmd().args([&a, &b]).assert().code(1);
+    let assert = cmd().args([&a, &b, "--normalize-symlinks"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("DIFFERENT-SYMLINK-TARGET"), "got:\n{}", output);
+}
+
+#[test]
+fn absolute_target_in_backup_matches_original() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[Dir("lib"), File("lib/x", "x")],
+        &[Dir("lib"), File("lib/x", "x")],
+    );
+    std::os::unix::fs::symlink(format!("{}/lib/x", a), format!("{}/link", a)).unwrap();
+    std::os::unix::fs::symlink(format!("{}/lib/x", b), format!("{}/link", b)).unwrap();
+
+    cmd().args([&a, &b, "--normalize-symlinks"]).assert().success();
+}
+
+#[test]
+fn targets_outside_the_trees_compare_as_absolute() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[Sym("link", "/usr/bin/env")], &[Sym("link", "/usr/bin/../bin/env")]);
+    cmd().args([&a, &b, "--normalize-symlinks"]).assert().success();
+
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[Sym("link", "/usr/bin/env")], &[Sym("link", "/usr/local/bin/env")]);
+    let assert = cmd().args([&a, &b, "--normalize-symlinks"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-SYMLINK-TARGET:", "normalized: \"/usr/bin/env\" vs \"/usr/local/bin/env\""),
+        "got:\n{}",
+        output
+    );
+}
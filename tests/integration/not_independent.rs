@@ -0,0 +1,55 @@
+//! Tests for NOT-INDEPENDENT: files that are the same file (same device and
+//! inode) in the original and the backup, e.g. from `cp -al`.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+
+#[test]
+fn hardlinked_file_is_not_independent() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "x"), File("g.txt", "y")], &[File("g.txt", "y")]);
+    std::fs::hard_link(format!("{}/f.txt", a), format!("{}/f.txt", b)).unwrap();
+
+    let assert = cmd().args([&a, &b, "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "NOT-INDEPENDENT:", "f.txt] (same file as"), "got:\n{}", output);
+    assert!(!some_line_has(&output, "NOT-INDEPENDENT:", "g.txt"), "got:\n{}", output);
+    assert!(output.contains("Not independent: 1"), "got:\n{}", output);
+    assert!(output.contains("WARNING: These files are shared"), "got:\n{}", output);
+    // root + g.txt; the hardlinked file isn't a similarity.
+    assert!(output.contains("Similarities: 2"), "got:\n{}", output);
+}
+
+#[test]
+fn copies_are_independent() {
+    let entries = &[File("f.txt", "x")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd().args([&a, &b]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("NOT-INDEPENDENT"), "got:\n{}", output);
+    assert!(!output.contains("Not independent"), "got:\n{}", output);
+}
+
+#[test]
+fn same_directory_is_not_independent() {
+    let (_tmp, a, _b) = setup_legacy_test_dirs(&[File("f.txt", "x")], &[]);
+    let assert = cmd().args([&a, &a]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("Not independent: 1"), "got:\n{}", output);
+}
+
+#[test]
+fn allow_not_independent_exits_0() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "x")], &[]);
+    std::fs::hard_link(format!("{}/f.txt", a), format!("{}/f.txt", b)).unwrap();
+
+    let assert = cmd().args([&a, &b, "--allow", "not-independent"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("Not independent: 1"), "got:\n{}", output);
+
+    let assert = cmd().args([&a, &b, "--warn-on", "not-independent"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("WARNING: 1 not-independent found"), "got:\n{}", output);
+
+    // Shares the different bit.
+    cmd().args([&a, &b, "--exit-bitmask"]).assert().code(4);
+}
@@ -0,0 +1,171 @@
+//! Tests for the exit-code policy: --fail-on, --warn-on, --allow and --exit-bitmask.
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, stdout_of};
+use predicates::prelude::*;
+
+#[test]
+fn allow_extras_exits_0() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("same.txt", "x")],
+        &[File("same.txt", "x"), File("extra.txt", "e")],
+    );
+    let assert = cmd().args([&a, &b, "--allow", "extras"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("EXTRA-FILE:"), "extras should still be reported, got:\n{}", output);
+    assert!(output.contains("Extras: 1"), "got:\n{}", output);
+    assert!(!output.contains("WARNING:"), "got:\n{}", output);
+}
+
+#[test]
+fn allow_does_not_hide_other_categories() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("missing.txt", "m")],
+        &[File("extra.txt", "e")],
+    );
+    cmd().args([&a, &b, "--allow", "extras"]).assert().code(1);
+}
+
+#[test]
+fn warn_on_extras_prints_warning_and_exits_0() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[File("e1.txt", "1"), File("e2.txt", "2")]);
+    let assert = cmd().args([&a, &b, "--warn-on", "extras"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains("WARNING: 2 extras found, not failing because of --warn-on extras"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn allow_special_with_fifo_exits_0() {
+    let entries = &[Fifo("pipe")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd().args([&a, &b, "--allow", "special"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("Special files: 2"), "got:\n{}", output);
+}
+
+#[test]
+fn allow_dangling_does_not_allow_other_errors() {
+    let entries = &[Sym("link", "nowhere")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd()
+        .args([&a, &b, "--follow", "--allow", "dangling"])
+        .assert()
+        .success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("Errors: 2"), "got:\n{}", output);
+    assert!(output.contains("Dangling symlinks: 2"), "got:\n{}", output);
+
+    // Without --allow dangling, the same tree fails.
+    cmd().args([&a, &b, "--follow"]).assert().code(1);
+}
+
+#[test]
+fn allow_cycles_exits_0() {
+    let entries = &[Dir("sub"), Sym("sub/loop", "..")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd().args([&a, &b, "--follow", "--allow", "cycles"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("Symlink cycles: 2"), "got:\n{}", output);
+
+    // Cycles share the errors bit, keeping bitmask codes below 126.
+    cmd().args([&a, &b, "--follow", "--exit-bitmask"]).assert().code(32);
+}
+
+#[test]
+fn fail_on_skipped() {
+    let entries = &[Dir("sub"), File("sub/f.txt", "x")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let ignore = format!("{}/sub", a);
+    cmd().args([&a, &b, "-i", &ignore]).assert().success();
+    cmd().args([&a, &b, "-i", &ignore, "--fail-on", "skipped"]).assert().code(1);
+}
+
+#[test]
+fn comma_separated_categories() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("missing.txt", "m")],
+        &[File("extra.txt", "e")],
+    );
+    cmd().args([&a, &b, "--allow", "missing,extras"]).assert().success();
+}
+
+#[test]
+fn exit_bitmask_encodes_failing_categories() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("missing.txt", "m"), File("diff.txt", "short")],
+        &[File("extra.txt", "e"), File("diff.txt", "longer")],
+    );
+    // missing=1 | different=4 | extras=8
+    cmd().args([&a, &b, "--exit-bitmask"]).assert().code(13);
+    // Allowed and warned categories don't set bits.
+    cmd()
+        .args([&a, &b, "--exit-bitmask", "--allow", "missing", "--warn-on", "extras"])
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn exit_bitmask_avoids_reserved_codes() {
+    // A lone difference must not look like the usage-error code 2.
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("diff.txt", "short")], &[File("diff.txt", "longer")]);
+    cmd().args([&a, &b, "--exit-bitmask"]).assert().code(4);
+
+    // Dangling symlinks share the errors bit.
+    let entries = &[Sym("dangling", "nowhere")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    cmd().args([&a, &b, "--follow", "--exit-bitmask"]).assert().code(32);
+}
+
+#[test]
+fn exit_bitmask_with_cycles_stays_below_126() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("missing.txt", "m"), File("diff.txt", "short"), File("skip.txt", "s"), Dir("sub"), Sym("sub/loop", "..")],
+        &[File("extra.txt", "e"), File("diff.txt", "longer"), File("skip.txt", "s"), Dir("sub"), Sym("sub/loop", "..")],
+    );
+    let skip = format!("{}/skip.txt", a);
+    // missing=1 | different=4 | extras=8 | cycles=32 | skipped=64
+    cmd()
+        .args([&a, &b, "--follow", "-i", &skip, "--fail-on", "skipped", "--exit-bitmask"])
+        .assert()
+        .code(109);
+}
+
+#[test]
+fn exit_bitmask_exits_0_when_identical() {
+    let entries = &[File("f.txt", "x")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    cmd().args([&a, &b, "--exit-bitmask"]).assert().success();
+}
+
+#[test]
+fn fail_fast_ignores_allowed_categories() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[File("e1.txt", "1"), File("e2.txt", "2")]);
+    let assert = cmd()
+        .args([&a, &b, "--fail-fast", "--allow", "extras"])
+        .assert()
+        .success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("ABORTED:"), "got:\n{}", output);
+    let extra_lines = output.lines().filter(|l| l.starts_with("EXTRA-FILE:")).count();
+    assert_eq!(extra_lines, 2, "got:\n{}", output);
+}
+
+#[test]
+fn conflicting_policy_exits_2() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    cmd()
+        .args([&a, &b, "--fail-on", "extras", "--allow", "extras"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("given to both --fail-on and --allow"));
+}
+
+#[test]
+fn unknown_category_exits_2() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    cmd().args([&a, &b, "--allow", "bogus"]).assert().code(2);
+}
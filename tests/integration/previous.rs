@@ -0,0 +1,161 @@
+//! Tests for --previous, which classifies DIFFERENT and MISSING entries by
+//! comparing the original with the previous backup.
+
+use super::harness::{create_entries, setup_legacy_test_dirs, Entry, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use predicates::prelude::*;
+
+/// Create original `a`, current backup `b` and previous backup `p`.
+fn setup_with_previous(
+    orig: &[Entry],
+    backup: &[Entry],
+    previous: &[Entry],
+) -> (tempfile::TempDir, String, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(orig, backup);
+    let p = tmp.path().join("p");
+    std::fs::create_dir_all(&p).unwrap();
+    create_entries(&p, previous);
+    let p = p.to_str().unwrap().to_string();
+    (tmp, a, b, p)
+}
+
+#[test]
+fn changed_at_source() {
+    let (_tmp, a, b, p) = setup_with_previous(
+        &[File("f.txt", "new content!")],
+        &[File("f.txt", "old")],
+        &[File("f.txt", "old")],
+    );
+    let assert = cmd().args([&a, &b, "--previous", &p]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-FILE [SIZE]:", "f.txt] (changed at source)"),
+        "got:\n{}",
+        output
+    );
+    assert!(output.contains("Changed at source: 1"), "got:\n{}", output);
+    assert!(output.contains("Corrupted in backup: 0"), "got:\n{}", output);
+}
+
+#[test]
+fn corrupted_in_backup() {
+    let (_tmp, a, b, p) = setup_with_previous(
+        &[File("f.txt", "aaaa")],
+        &[File("f.txt", "aaaaaa")],
+        &[File("f.txt", "aaaa")],
+    );
+    let assert = cmd().args([&a, &b, "--previous", &p]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-FILE [SIZE]:", "f.txt] (corrupted in backup)"),
+        "got:\n{}",
+        output
+    );
+    assert!(output.contains("Corrupted in backup: 1"), "got:\n{}", output);
+}
+
+#[test]
+fn corrupted_in_backup_with_hash() {
+    // Same size, so only --all can tell the files apart.
+    let (_tmp, a, b, p) = setup_with_previous(
+        &[File("f.txt", "aaaa")],
+        &[File("f.txt", "abba")],
+        &[File("f.txt", "aaaa")],
+    );
+    let assert = cmd().args([&a, &b, "--previous", &p, "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-FILE [HASH]:", "(corrupted in backup)"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn changed_at_source_with_hash() {
+    let (_tmp, a, b, p) = setup_with_previous(
+        &[File("f.txt", "bbbb")],
+        &[File("f.txt", "aaaa")],
+        &[File("f.txt", "aaaa")],
+    );
+    let assert = cmd().args([&a, &b, "--previous", &p, "--all"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-FILE [HASH]:", "(changed at source)"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn missing_new_since_last_backup() {
+    let (_tmp, a, b, p) = setup_with_previous(&[File("new.txt", "n")], &[], &[]);
+    let assert = cmd().args([&a, &b, "--previous", &p]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "MISSING-FILE:", "new.txt] (new since last backup)"),
+        "got:\n{}",
+        output
+    );
+    assert!(output.contains("New since last backup: 1"), "got:\n{}", output);
+}
+
+#[test]
+fn missing_dir_lost_from_backup() {
+    let (_tmp, a, b, p) = setup_with_previous(
+        &[Dir("docs"), File("docs/a.txt", "a")],
+        &[],
+        &[Dir("docs"), File("docs/a.txt", "a")],
+    );
+    let assert = cmd().args([&a, &b, "--previous", &p]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "MISSING-DIR:", "docs] (corrupted in backup)"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn symlink_target_changed_at_source() {
+    let (_tmp, a, b, p) = setup_with_previous(
+        &[Sym("link", "new_target")],
+        &[Sym("link", "old_target")],
+        &[Sym("link", "old_target")],
+    );
+    let assert = cmd().args([&a, &b, "--previous", &p]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        some_line_has(&output, "DIFFERENT-SYMLINK-TARGET:", "(changed at source)"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn extras_are_not_classified() {
+    let (_tmp, a, b, p) = setup_with_previous(&[], &[File("extra.txt", "e")], &[]);
+    let assert = cmd().args([&a, &b, "--previous", &p]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(output.lines().any(|l| l.starts_with("EXTRA-FILE:") && l.ends_with("extra.txt]")), "got:\n{}", output);
+    assert!(!output.contains("Changed at source:"), "got:\n{}", output);
+}
+
+#[test]
+fn no_notes_without_previous() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "aaaa")], &[File("f.txt", "aaaaaa")]);
+    let assert = cmd().args([&a, &b]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(!output.contains("(corrupted in backup)"), "got:\n{}", output);
+    assert!(!output.contains("(changed at source)"), "got:\n{}", output);
+}
+
+#[test]
+fn nonexistent_previous_exits_2() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    cmd()
+        .args([&a, &b, "--previous", "/nonexistent/dir/previous"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Cannot resolve previous backup directory"));
+}
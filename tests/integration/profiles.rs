@@ -0,0 +1,286 @@
+//! Tests for --profile, which reads options and roots from a TOML config file.
+
+use super::harness::{create_entries, setup_legacy_test_dirs, Entry::*};
+use super::{cmd, some_line_has, stdout_of};
+use predicates::prelude::*;
+
+/// Write `contents` as the config file next to the test directories.
+fn write_config(tmp: &tempfile::TempDir, contents: &str) -> String {
+    let path = tmp.path().join("profiles.toml");
+    std::fs::write(&path, contents).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn profile_supplies_roots_and_options() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "aaaa")], &[File("f.txt", "abba")]);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.nightly]\noriginal = {:?}\nbackups = [{:?}]\nall = true\n", a, b),
+    );
+    let assert = cmd().args(["--profile", "nightly", "--config", &config]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [HASH]:", "f.txt"), "got:\n{}", output);
+}
+
+#[test]
+fn command_line_overrides_profile() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "x")], &[File("f.txt", "x")]);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nprevious = \"/nonexistent/previous\"\n", a, b),
+    );
+    // The profile's --previous doesn't exist, but the command line's replaces it.
+    cmd()
+        .args(["--profile", "p", "--config", &config, "--previous", &b])
+        .assert()
+        .success();
+    cmd()
+        .args(["--profile", "p", "--config", &config])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Cannot resolve previous backup directory"));
+}
+
+#[test]
+fn ignore_lists_are_combined() {
+    let (tmp, a, b) = setup_legacy_test_dirs(
+        &[File("x.txt", "x"), File("y.txt", "y")],
+        &[File("x.txt", "xx"), File("y.txt", "yy")],
+    );
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nignore = [\"{}/x.txt\"]\n", a, b, a),
+    );
+    let ignore_y = format!("{}/y.txt", a);
+    let assert = cmd()
+        .args(["--profile", "p", "--config", &config, "--ignore", &ignore_y])
+        .assert()
+        .success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("DIFFERENT-FILE"), "got:\n{}", output);
+}
+
+#[test]
+fn command_line_roots_replace_profile_roots() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "x")], &[File("f.txt", "x")]);
+    let config = write_config(
+        &tmp,
+        "[profiles.p]\noriginal = \"/nonexistent/original\"\nbackups = [\"/nonexistent/backup\"]\n",
+    );
+    cmd().args(["--profile", "p", "--config", &config, &a, &b]).assert().success();
+}
+
+#[test]
+fn several_root_pairs() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "x")], &[File("f.txt", "x")]);
+    let c = tmp.path().join("c");
+    let d = tmp.path().join("d");
+    std::fs::create_dir_all(&c).unwrap();
+    std::fs::create_dir_all(&d).unwrap();
+    create_entries(&c, &[File("lost.txt", "l")]);
+    let (c, d) = (c.to_str().unwrap(), d.to_str().unwrap());
+
+    let config = write_config(
+        &tmp,
+        &format!(
+            "[profiles.p]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackups = [{:?}]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackup = {:?}\n",
+            a, b, c, d
+        ),
+    );
+    let assert = cmd().args(["--profile", "p", "--config", &config]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains(&format!("ROOTS 1 of 2: [{}]", a)), "got:\n{}", output);
+    assert!(output.contains(&format!("ROOTS 2 of 2: [{}]", c)), "got:\n{}", output);
+    assert!(some_line_has(&output, "MISSING-FILE:", "lost.txt"), "got:\n{}", output);
+    assert!(output.contains("SUMMARY (all roots):"), "got:\n{}", output);
+}
+
+#[test]
+fn invalid_value_points_at_line() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nsamples = \"lots\"\n", a, b),
+    );
+    cmd()
+        .args(["--profile", "p", "--config", &config])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(format!("{}:4:", config)))
+        .stderr(predicate::str::contains("--samples"));
+}
+
+#[test]
+fn unknown_key_points_at_line() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\n\nsampels = 3\n", a, b),
+    );
+    cmd()
+        .args(["--profile", "p", "--config", &config])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(format!("{}:5: unknown key \"sampels\"", config)));
+}
+
+#[test]
+fn unknown_profile_exits_2() {
+    let (tmp, _a, _b) = setup_legacy_test_dirs(&[], &[]);
+    let config = write_config(&tmp, "[profiles.nightly]\nall = true\n");
+    cmd()
+        .args(["--profile", "weekly", "--config", &config])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("No profile named \"weekly\""));
+}
+
+/// Two root pairs (a, b) and (c, d), where x.txt differs in both.
+fn setup_two_pairs() -> (tempfile::TempDir, String, String, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("x.txt", "x")], &[File("x.txt", "xx")]);
+    let c = tmp.path().join("c");
+    let d = tmp.path().join("d");
+    std::fs::create_dir_all(&c).unwrap();
+    std::fs::create_dir_all(&d).unwrap();
+    create_entries(&c, &[File("x.txt", "x")]);
+    create_entries(&d, &[File("x.txt", "xx")]);
+    let (c, d) = (c.to_str().unwrap().to_string(), d.to_str().unwrap().to_string());
+    (tmp, a, b, c, d)
+}
+
+#[test]
+fn roots_tables_have_their_own_ignore() {
+    let (tmp, a, b, c, d) = setup_two_pairs();
+    let config = write_config(
+        &tmp,
+        &format!(
+            "[profiles.p]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackups = [{:?}]\nignore = [\"{}/x.txt\"]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackup = {:?}\nignore = \"{}/x.txt\"\n",
+            a, b, a, c, d, c
+        ),
+    );
+    let assert = cmd().args(["--profile", "p", "--config", &config]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("DIFFERENT-FILE"), "got:\n{}", output);
+}
+
+#[test]
+fn profile_ignore_only_applies_to_its_root_pair() {
+    let (tmp, a, b, c, d) = setup_two_pairs();
+    let config = write_config(
+        &tmp,
+        &format!(
+            "[profiles.p]\nignore = [\"{}/x.txt\"]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackups = [{:?}]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackup = {:?}\n",
+            a, a, b, c, d
+        ),
+    );
+    // Ignored in the first pair, still compared in the second.
+    let assert = cmd().args(["--profile", "p", "--config", &config]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(!some_line_has(&output, "DIFFERENT-FILE", &format!("{}/x.txt", a)), "got:\n{}", output);
+    assert!(some_line_has(&output, "DIFFERENT-FILE", &format!("{}/x.txt", c)), "got:\n{}", output);
+
+    // The same goes for --ignore on the command line.
+    let ignore_c = format!("{}/x.txt", c);
+    let assert = cmd()
+        .args(["--profile", "p", "--config", &config, "--ignore", &ignore_c])
+        .assert()
+        .success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("DIFFERENT-FILE"), "got:\n{}", output);
+}
+
+#[test]
+fn ignore_outside_every_root_pair_exits_2() {
+    let (tmp, a, b, c, d) = setup_two_pairs();
+    let config = write_config(
+        &tmp,
+        &format!(
+            "[profiles.p]\nignore = [\"/nonexistent/x.txt\"]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackups = [{:?}]\n\
+             [[profiles.p.roots]]\noriginal = {:?}\nbackup = {:?}\n",
+            a, b, c, d
+        ),
+    );
+    cmd()
+        .args(["--profile", "p", "--config", &config])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("is not within any root pair"));
+}
+
+#[test]
+fn command_line_count_replaces_profile_count() {
+    let entries = &[Dir("sub"), File("sub/f.txt", "x")];
+    let (tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nverbose = 2\n", a, b),
+    );
+    let assert = cmd().args(["--profile", "p", "--config", &config]).assert().success();
+    assert!(stdout_of(&assert).contains("DEBUG: Comparing file"));
+
+    // -v replaces verbose = 2 instead of making it 3: dirs only.
+    let assert = cmd().args(["--profile", "p", "--config", &config, "-v"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("DEBUG: Comparing ["), "got:\n{}", output);
+    assert!(!output.contains("DEBUG: Comparing file"), "got:\n{}", output);
+}
+
+#[test]
+fn no_flag_turns_off_profile_flag() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "aaaa")], &[File("f.txt", "abba")]);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nall = true\n", a, b),
+    );
+    cmd().args(["--profile", "p", "--config", &config]).assert().code(1);
+    cmd().args(["--profile", "p", "--config", &config, "--no-all"]).assert().success();
+
+    // Without a profile, --no-FLAG turns off the flag given on the command line.
+    cmd().args([&a, &b, "-a", "--no-all"]).assert().success();
+    cmd()
+        .args([&a, &b, "--no-samples"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--no-samples"));
+}
+
+#[test]
+fn options_that_require_others_can_be_used() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[File("f.txt", "x")], &[File("f.txt", "x")]);
+    let config = write_config(
+        &tmp,
+        &format!(
+            "[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nfollow = true\nfollow_within_root = true\nmax_follow_depth = 2\n",
+            a, b
+        ),
+    );
+    cmd().args(["--profile", "p", "--config", &config]).assert().success();
+}
+
+#[test]
+fn missing_required_option_points_at_line() {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let config = write_config(
+        &tmp,
+        &format!("[profiles.p]\noriginal = {:?}\nbackups = [{:?}]\nsamples = 2\nfollow_within_root = true\n", a, b),
+    );
+    cmd()
+        .args(["--profile", "p", "--config", &config])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(format!(
+            "{}:5: the following required arguments were not provided: --follow",
+            config
+        )));
+
+    // The command line can supply what the profile's options require.
+    cmd().args(["--profile", "p", "--config", &config, "--follow"]).assert().success();
+}
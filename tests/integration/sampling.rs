@@ -0,0 +1,209 @@
+//! Tests for --sample-size, --sample-strategy and --seed.
+
+use super::harness::{setup_file_pair, setup_legacy_test_dirs};
+use super::{cmd, some_line_has, stdout_of};
+use predicates::prelude::*;
+
+#[test]
+fn edges_catch_zeroed_header() {
+    let orig = vec![b'a'; 1 << 20];
+    let mut backup = orig.clone();
+    backup[..16].fill(0);
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    let assert = cmd()
+        .args([&a, &b, "--samples", "2", "--sample-strategy", "edges"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [SAMPLE]:", "f.bin"), "got:\n{}", output);
+}
+
+#[test]
+fn edges_catch_zeroed_tail() {
+    let orig = vec![b'a'; 1 << 20];
+    let mut backup = orig.clone();
+    let len = backup.len();
+    backup[len - 1] = 0;
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    cmd()
+        .args([&a, &b, "--samples", "2", "--sample-strategy", "edges"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn stratified_covers_every_part() {
+    // Only the last 64 KiB differ. Sixteen random 64 KiB samples miss them
+    // about a third of the time, but the last of sixteen stratified samples
+    // always overlaps them.
+    let orig = vec![b'a'; 1 << 20];
+    let mut backup = orig.clone();
+    let len = backup.len();
+    backup[len - (64 << 10)..].fill(b'b');
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    cmd()
+        .args([&a, &b, "--samples", "16", "--sample-size", "65536", "--sample-strategy", "stratified"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn sample_size_larger_than_file() {
+    let (_tmp, a, b) = setup_file_pair("f.bin", b"abc", b"abd");
+    cmd()
+        .args([&a, &b, "--samples", "1", "--sample-size", "4096"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("DIFFERENT-FILE [SAMPLE]:"));
+}
+
+#[test]
+fn summary_shows_sampling() {
+    let (_tmp, a, b) = setup_file_pair("f.bin", b"same", b"same");
+    let assert = cmd()
+        .args([&a, &b, "--samples", "3", "--sample-size", "8", "--sample-strategy", "scaled"])
+        .assert()
+        .success();
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains("    Sampling: 3 samples of 8 bytes per started GiB of each file (scaled), --seed "),
+        "got:\n{}",
+        output
+    );
+
+    let assert = cmd().args([&a, &b, "--samples", "2"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(output.contains("    Sampling: 2 samples of 32 bytes per file (random), --seed "), "got:\n{}", output);
+
+    let assert = cmd().args([&a, &b]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("Sampling:"), "got:\n{}", output);
+}
+
+#[test]
+fn zero_sample_size_exits_2() {
+    let (_tmp, a, b) = setup_file_pair("f.bin", b"x", b"x");
+    cmd().args([&a, &b, "--samples", "1", "--sample-size", "0"]).assert().code(2);
+}
+
+/// One differing byte in 1 MiB, which a single 64 KiB sample finds one time
+/// in sixteen.
+fn setup_one_byte_differs() -> (tempfile::TempDir, String, String) {
+    let orig = vec![b'a'; 1 << 20];
+    let mut backup = orig.clone();
+    backup[1 << 19] = b'b';
+    setup_file_pair("f.bin", &orig, &backup)
+}
+
+fn sampled_exit_code(a: &str, b: &str, extra: &[&str]) -> (i32, String) {
+    let output = cmd()
+        .args([a, b, "--samples", "1", "--sample-size", "65536"])
+        .args(extra)
+        .output()
+        .unwrap();
+    (output.status.code().unwrap(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Samples are read a piece at a time and compared as they're read, so huge
+/// samples of a huge file don't need memory for all of them at once.
+#[test]
+fn huge_samples_are_read_in_pieces() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    for dir in [&a, &b] {
+        let file = std::fs::File::create(format!("{}/f.bin", dir)).unwrap();
+        file.set_len(2 << 30).unwrap();
+    }
+    // The lowest of the many samples starts before this byte.
+    let mut backup = std::fs::File::options().write(true).open(format!("{}/f.bin", b)).unwrap();
+    std::io::Seek::seek(&mut backup, std::io::SeekFrom::Start(1 << 20)).unwrap();
+    std::io::Write::write_all(&mut backup, b"x").unwrap();
+
+    let assert = cmd()
+        .args([&a, &b, "--samples", "100000", "--sample-size", "1073741824"])
+        .assert()
+        .code(1);
+    let output = stdout_of(&assert);
+    assert!(some_line_has(&output, "DIFFERENT-FILE [SAMPLE]:", "f.bin"), "got:\n{}", output);
+}
+
+#[test]
+fn seed_is_shown_before_an_abort() {
+    let (_tmp, a, b) = setup_file_pair("f.bin", &[b'a'; 1 << 20], &[b'b'; 1 << 20]);
+    for dir in [&a, &b] {
+        std::fs::write(format!("{}/g.bin", dir), b"g").unwrap();
+    }
+    let output = cmd()
+        .args([&a, &b, "--samples", "200", "--fail-fast"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(3), "got:\n{}", stdout);
+    let sampling = stdout.lines().position(|l| l.starts_with("SAMPLING: 200 samples of 32 bytes per file (random), --seed "));
+    let aborted = stdout.lines().position(|l| l.starts_with("ABORTED:"));
+    assert!(sampling.is_some() && sampling < aborted, "got:\n{}", stdout);
+}
+
+#[test]
+fn same_seed_samples_same_offsets() {
+    let (_tmp, a, b) = setup_one_byte_differs();
+
+    let mut found = None;
+    let mut missed = None;
+    for seed in 0..200u64 {
+        let (code, _) = sampled_exit_code(&a, &b, &["--seed", &seed.to_string()]);
+        match code {
+            1 => found = found.or(Some(seed)),
+            0 => missed = missed.or(Some(seed)),
+            _ => panic!("unexpected exit code {}", code),
+        }
+        if found.is_some() && missed.is_some() {
+            break;
+        }
+    }
+    let (found, missed) = (found.expect("no seed finds the difference"), missed.unwrap());
+
+    for _ in 0..3 {
+        assert_eq!(sampled_exit_code(&a, &b, &["--seed", &found.to_string()]).0, 1);
+        assert_eq!(sampled_exit_code(&a, &b, &["--seed", &missed.to_string()]).0, 0);
+    }
+}
+
+#[test]
+fn random_seed_is_shown_and_reproduces_the_run() {
+    let (_tmp, a, b) = setup_one_byte_differs();
+
+    let (code, output) = sampled_exit_code(&a, &b, &[]);
+    let seed = output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Sampling: ")?.split("--seed ").nth(1))
+        .unwrap_or_else(|| panic!("no seed in output:\n{}", output))
+        .to_string();
+    seed.parse::<u64>().unwrap();
+
+    let (rerun_code, rerun_output) = sampled_exit_code(&a, &b, &["--seed", &seed]);
+    assert_eq!(rerun_code, code);
+    assert!(rerun_output.contains(&format!("--seed {}", seed)), "got:\n{}", rerun_output);
+}
+
+#[test]
+fn many_overlapping_samples() {
+    // A thousand 32-byte samples of a 100-byte file overlap a lot, and all
+    // but a few miss the last byte.
+    let orig = vec![b'a'; 100];
+    let mut backup = orig.clone();
+    backup[99] = b'b';
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    cmd()
+        .args([&a, &b, "--samples", "1000", "--seed", "1"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("DIFFERENT-FILE [SAMPLE]:"));
+
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &orig);
+    cmd().args([&a, &b, "--samples", "1000"]).assert().success();
+}
@@ -0,0 +1,123 @@
+//! Tests for --superset, where the backup may contain entries that are not in
+//! the original. Extras are not walked, reported, or counted, but missing and
+//! different entries are still reported in full.
+//!
+//! These cases are not symmetric, since swapping the sides turns extras into
+//! missing entries.
+
+use super::harness::Entry::*;
+use crate::case;
+
+case!(superset_extras_not_reported {
+    orig: [File("same.txt", "x")],
+    backup: [
+        File("same.txt", "x"),
+        File("extra.txt", "e"),
+        Dir("extra_dir"),
+        File("extra_dir/f.txt", "f"),
+    ],
+    flags: ["--superset"],
+    lines: [],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["EXTRA-"],
+    original_processed: 2,
+    backup_processed: 2,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 0,
+    errors: 0,
+    symmetric: false,
+});
+
+case!(superset_extras_not_walked_with_vv {
+    orig: [],
+    backup: [Dir("extra_dir"), Dir("extra_dir/sub"), File("extra_dir/sub/f.txt", "f")],
+    flags: ["--superset", "-vv"],
+    lines: [],
+    debug_contains: [],
+    debug_excludes: ["extra_dir"],
+    output_contains: [],
+    output_excludes: ["EXTRA-", "extra_dir"],
+    original_processed: 1,
+    backup_processed: 1,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 1,
+    skipped: 0,
+    errors: 0,
+    symmetric: false,
+});
+
+case!(superset_missing_still_reported {
+    orig: [File("same.txt", "x"), Dir("gone"), File("gone/f.txt", "f")],
+    backup: [File("same.txt", "x"), File("extra.txt", "e")],
+    flags: ["--superset"],
+    lines: ["MISSING-DIR: a/gone"],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["EXTRA-"],
+    original_processed: 4,
+    backup_processed: 2,
+    missing: 2,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 0,
+    errors: 0,
+    symmetric: false,
+});
+
+case!(superset_different_still_reported {
+    orig: [Dir("sub"), File("sub/f.txt", "short")],
+    backup: [Dir("sub"), File("sub/f.txt", "much longer"), File("sub/extra.txt", "e")],
+    flags: ["--superset"],
+    lines: ["DIFFERENT-FILE [SIZE]: a/sub/f.txt"],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["EXTRA-"],
+    original_processed: 3,
+    backup_processed: 3,
+    missing: 0,
+    different: 1,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 0,
+    errors: 0,
+    symmetric: false,
+});
+
+// The backup side of a type mismatch is counted, but not reported as EXTRA or walked.
+case!(superset_file_dir_mismatch {
+    orig: [File("x", "content")],
+    backup: [Dir("x"), File("x/inner.txt", "i")],
+    flags: ["--superset"],
+    lines: [
+        "FILE-DIR-MISMATCH: a/x",
+        "MISSING-FILE: a/x",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: [],
+    output_excludes: ["EXTRA-", "inner.txt"],
+    original_processed: 2,
+    backup_processed: 2,
+    missing: 1,
+    different: 1,
+    extras: 0,
+    special_files: 0,
+    similarities: 1,
+    skipped: 0,
+    errors: 0,
+    symmetric: false,
+});
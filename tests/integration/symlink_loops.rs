@@ -3,6 +3,8 @@
 //! Tests verify that symlink loops are handled gracefully:
 //! - Without --follow: loops are not a problem (symlinks compared by target)
 //! - With --follow: loops produce ERROR with "Too many levels of symbolic links"
+//! - With --follow: symlinks to an ancestor directory (e.g. `loop -> ..`) never
+//!   produce ELOOP, so they're reported as SYMLINK-CYCLE and not descended into
 
 use super::harness::Entry::*;
 use crate::case;
@@ -281,3 +283,144 @@ case!(valid_in_orig_loop_in_backup {
     errors: 1,
     symmetric: false,
 });
+
+// ===========================================================================
+// With --follow: symlinks to an ancestor directory are SYMLINK-CYCLE
+// ===========================================================================
+
+// Symlink to the parent directory: resolving works fine, but descending into
+// it would repeat the tree forever.
+case!(parent_cycle_with_follow {
+    orig: [
+        File("ok.txt", "ok\n"),
+        Dir("sub"),
+        Sym("sub/loop", ".."),
+    ],
+    backup: [
+        File("ok.txt", "ok\n"),
+        Dir("sub"),
+        Sym("sub/loop", ".."),
+    ],
+    flags: ["--follow"],
+    lines: [
+        "SYMLINK-CYCLE: a/sub/loop",
+        "SYMLINK-CYCLE: b/sub/loop",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["Symlink cycles: 2", "Errors: 2"],
+    output_excludes: [],
+    // root + ok.txt + sub + sub/loop + resolved sub/loop (cycle)
+    original_processed: 5,
+    backup_processed: 5,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    // root + ok.txt + sub + sub/loop
+    similarities: 4,
+    skipped: 0,
+    errors: 2,
+    symmetric: false,
+});
+
+// Symlink to its own directory
+case!(self_dir_cycle_with_follow {
+    orig: [
+        Sym("here", "."),
+    ],
+    backup: [
+        Sym("here", "."),
+    ],
+    flags: ["--follow"],
+    lines: [
+        "SYMLINK-CYCLE: a/here",
+        "SYMLINK-CYCLE: b/here",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["Symlink cycles: 2"],
+    output_excludes: [],
+    original_processed: 3,
+    backup_processed: 3,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 0,
+    errors: 2,
+    symmetric: false,
+});
+
+// Cycle through a second followed symlink: sub/link -> ../other, and
+// other/back -> .. points at the root, which is an ancestor of sub/link/back.
+case!(cycle_through_followed_symlink {
+    orig: [
+        Dir("other"),
+        Sym("other/back", ".."),
+        Dir("sub"),
+        Sym("sub/link", "../other"),
+    ],
+    backup: [
+        Dir("other"),
+        Sym("other/back", ".."),
+        Dir("sub"),
+        Sym("sub/link", "../other"),
+    ],
+    flags: ["--follow"],
+    lines: [
+        "SYMLINK-CYCLE: a/other/back",
+        "SYMLINK-CYCLE: b/other/back",
+        "SYMLINK-CYCLE: a/sub/link/back",
+        "SYMLINK-CYCLE: b/sub/link/back",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["Symlink cycles: 4"],
+    output_excludes: [],
+    // root + other + other/back (+ cycle) + sub + sub/link (+ resolved) + sub/link/back (+ cycle)
+    original_processed: 9,
+    backup_processed: 9,
+    missing: 0,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    // root + other + other/back + sub + sub/link + resolved sub/link + sub/link/back
+    similarities: 7,
+    skipped: 0,
+    errors: 4,
+    symmetric: false,
+});
+
+// Cycle only in the original: the symlink is missing from the backup, and
+// the walk of the missing entry stops at the cycle.
+case!(cycle_in_orig_missing_from_backup {
+    orig: [
+        Dir("sub"),
+        Sym("sub/loop", ".."),
+    ],
+    backup: [
+        Dir("sub"),
+    ],
+    flags: ["--follow"],
+    lines: [
+        "MISSING-SYMLINK: a/sub/loop",
+        "SYMLINK-CYCLE: a/sub/loop",
+    ],
+    debug_contains: [],
+    debug_excludes: [],
+    output_contains: ["Symlink cycles: 1"],
+    output_excludes: [],
+    // root + sub + sub/loop + resolved sub/loop (cycle)
+    original_processed: 4,
+    backup_processed: 2,
+    missing: 1,
+    different: 0,
+    extras: 0,
+    special_files: 0,
+    similarities: 2,
+    skipped: 0,
+    errors: 1,
+    symmetric: false,
+});
@@ -0,0 +1,108 @@
+//! Tests for the abort thresholds: --max-errors, --max-missing-percent and --fail-fast.
+//!
+//! When a threshold trips, vfy stops the walk, prints the partial summary and
+//! exits with code 3 (verification incomplete).
+
+use super::harness::{setup_legacy_test_dirs, Entry::*};
+use super::{cmd, stdout_of};
+use predicates::prelude::*;
+
+/// Create `total` files named f000.. in the original and the first `present`
+/// of them in the backup.
+fn setup_many_files(total: usize, present: usize) -> (tempfile::TempDir, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    for i in 0..total {
+        let name = format!("f{:03}", i);
+        std::fs::write(std::path::Path::new(&a).join(&name), "x").unwrap();
+        if i < present {
+            std::fs::write(std::path::Path::new(&b).join(&name), "x").unwrap();
+        }
+    }
+    (tmp, a, b)
+}
+
+#[test]
+fn fail_fast_stops_at_first_difference() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(
+        &[File("a.txt", "a"), File("b.txt", "b"), File("c.txt", "c")],
+        &[],
+    );
+    let assert = cmd().args([&a, &b, "--fail-fast"]).assert().code(3);
+    let output = stdout_of(&assert);
+
+    let missing_lines = output.lines().filter(|l| l.starts_with("MISSING-FILE:")).count();
+    assert_eq!(missing_lines, 1, "should stop after the first MISSING, got:\n{}", output);
+    assert!(output.contains("ABORTED: difference found with --fail-fast"), "got:\n{}", output);
+    assert!(output.contains("SUMMARY:"), "partial summary should be printed, got:\n{}", output);
+    assert!(output.contains("WARNING: EXITING BEFORE VERIFICATION WAS COMPLETE!"), "got:\n{}", output);
+    assert!(!output.contains("COMPARISON FINISHED!"), "got:\n{}", output);
+}
+
+#[test]
+fn fail_fast_exit_0_when_identical() {
+    let entries = &[File("a.txt", "a"), Dir("sub"), File("sub/b.txt", "b")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd().args([&a, &b, "--fail-fast"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("ABORTED:"), "got:\n{}", output);
+    assert!(output.contains("COMPARISON FINISHED!"), "got:\n{}", output);
+}
+
+#[test]
+fn max_errors_stops_after_threshold() {
+    // With --follow, each dangling symlink counts one error per side.
+    let entries = &[
+        Sym("l1", "nowhere1"),
+        Sym("l2", "nowhere2"),
+        Sym("l3", "nowhere3"),
+    ];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd().args([&a, &b, "--follow", "--max-errors", "1"]).assert().code(3);
+    let output = stdout_of(&assert);
+
+    assert!(output.contains("ABORTED: 2 errors exceeds --max-errors 1"), "got:\n{}", output);
+    assert!(!output.contains("l2"), "should stop before l2, got:\n{}", output);
+    assert!(output.contains("Errors: 2"), "got:\n{}", output);
+}
+
+#[test]
+fn max_errors_not_exceeded_completes() {
+    let entries = &[Sym("l1", "nowhere1")];
+    let (_tmp, a, b) = setup_legacy_test_dirs(entries, entries);
+    let assert = cmd().args([&a, &b, "--follow", "--max-errors", "2"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(!output.contains("ABORTED:"), "got:\n{}", output);
+    assert!(output.contains("COMPARISON FINISHED!"), "got:\n{}", output);
+}
+
+#[test]
+fn max_missing_percent_stops_after_threshold() {
+    // f000..f099 present, f100.. missing. The percentage is only checked once
+    // 100 original items have been processed.
+    let (_tmp, a, b) = setup_many_files(150, 100);
+    let assert = cmd().args([&a, &b, "--max-missing-percent", "1"]).assert().code(3);
+    let output = stdout_of(&assert);
+
+    assert!(output.contains("exceeds --max-missing-percent 1"), "got:\n{}", output);
+    let missing_lines = output.lines().filter(|l| l.starts_with("MISSING-FILE:")).count();
+    assert_eq!(missing_lines, 2, "got:\n{}", output);
+}
+
+#[test]
+fn max_missing_percent_ignored_for_small_trees() {
+    // Everything is missing, but fewer than 100 items were processed.
+    let (_tmp, a, b) = setup_many_files(10, 0);
+    let assert = cmd().args([&a, &b, "--max-missing-percent", "0"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(!output.contains("ABORTED:"), "got:\n{}", output);
+}
+
+#[test]
+fn max_missing_percent_out_of_range_exits_2() {
+    let (_tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    cmd()
+        .args([&a, &b, "--max-missing-percent", "150"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("between 0 and 100"));
+}
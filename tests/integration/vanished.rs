@@ -0,0 +1,81 @@
+//! Tests for VANISHED entries, deleted between listing their directory and
+//! comparing them. They watch vfy through /proc, so they only run on Linux.
+#![cfg(target_os = "linux")]
+
+use super::harness::{setup_legacy_test_dirs, wait_until_open};
+use std::process::{Command, Output};
+
+/// Run vfy on the original `args[0]` and the backup `args[1]`, and delete
+/// `path` while it's hashing the original's `a.big`. That sorts before every
+/// other entry and takes a while to hash one byte at a time, and by then both
+/// directories have been listed.
+fn run_deleting(args: &[&str], path: &str) -> (Output, String) {
+    let child = Command::new(env!("CARGO_BIN_EXE_vfy"))
+        .args(args)
+        .args(["--all", "--hash-mode", "read", "--hash-buffer", "1"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    wait_until_open(child.id(), &format!("{}/a.big", args[0]));
+    std::fs::remove_file(path).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    (output, stdout)
+}
+
+/// Both sides have `a.big`, plus `z.txt` on the sides given.
+fn setup(orig_z: bool, backup_z: bool) -> (tempfile::TempDir, String, String) {
+    let (tmp, a, b) = setup_legacy_test_dirs(&[], &[]);
+    let big = vec![b'a'; 512 << 10];
+    for (dir, z) in [(&a, orig_z), (&b, backup_z)] {
+        std::fs::write(format!("{}/a.big", dir), &big).unwrap();
+        if z {
+            std::fs::write(format!("{}/z.txt", dir), b"z").unwrap();
+        }
+    }
+    (tmp, a, b)
+}
+
+#[test]
+fn vanished_original_fails_by_default() {
+    let (_tmp, a, b) = setup(true, true);
+    let (output, stdout) = run_deleting(&[&a, &b], &format!("{}/z.txt", a));
+
+    assert_eq!(output.status.code(), Some(1), "got:\n{}", stdout);
+    assert!(
+        stdout.contains(&format!("VANISHED: [{}/z.txt] (deleted since its directory was listed)\n", a)),
+        "got:\n{}",
+        stdout
+    );
+    // The backup's copy isn't called an extra.
+    assert!(stdout.contains(&format!("SKIP: [{}/z.txt]\n", b)), "got:\n{}", stdout);
+    assert!(!stdout.contains("ERROR:"), "got:\n{}", stdout);
+    assert!(stdout.contains("    Errors: 0\n"), "got:\n{}", stdout);
+    assert!(stdout.contains("    Vanished: 1"), "got:\n{}", stdout);
+}
+
+#[test]
+fn allow_vanished_exits_0() {
+    let (_tmp, a, b) = setup(true, true);
+    let (output, stdout) = run_deleting(&[&a, &b, "--allow", "vanished"], &format!("{}/z.txt", a));
+    assert_eq!(output.status.code(), Some(0), "got:\n{}", stdout);
+    assert!(stdout.contains("VANISHED:"), "got:\n{}", stdout);
+}
+
+#[test]
+fn vanished_shares_the_errors_bit() {
+    let (_tmp, a, b) = setup(true, true);
+    let (output, stdout) = run_deleting(&[&a, &b, "--exit-bitmask"], &format!("{}/z.txt", a));
+    assert_eq!(output.status.code(), Some(32), "got:\n{}", stdout);
+}
+
+#[test]
+fn vanished_extra_is_not_extra() {
+    let (_tmp, a, b) = setup(false, true);
+    let (output, stdout) = run_deleting(&[&a, &b, "--warn-on", "vanished"], &format!("{}/z.txt", b));
+
+    assert_eq!(output.status.code(), Some(0), "got:\n{}", stdout);
+    assert!(stdout.contains(&format!("VANISHED: [{}/z.txt]", b)), "got:\n{}", stdout);
+    assert!(!stdout.contains("EXTRA-"), "got:\n{}", stdout);
+    assert!(stdout.contains("WARNING: 1 vanished found"), "got:\n{}", stdout);
+}
@@ -0,0 +1,117 @@
+//! Tests for --check-zeroed.
+
+use super::harness::{overwrite_keeping_mtime, setup_file_pair};
+use super::{cmd, stdout_of};
+
+/// 256 KiB of `a`, and a copy with bytes 64 KiB..128 KiB zeroed.
+fn zeroed_copy() -> (Vec<u8>, Vec<u8>) {
+    let orig = vec![b'a'; 256 << 10];
+    let mut backup = orig.clone();
+    backup[64 << 10..128 << 10].fill(0);
+    (orig, backup)
+}
+
+#[test]
+fn found_without_reading_contents_otherwise() {
+    let (orig, backup) = zeroed_copy();
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    cmd().args([&a, &b]).assert().success();
+
+    let assert = cmd().args([&a, &b, "--check-zeroed"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains(&format!("ZEROED: [{}/f.bin] 1 zero-filled ranges (65536 bytes): 65536..131072\n", b)),
+        "got:\n{}",
+        output
+    );
+    assert!(output.contains("    Different: 1 "), "got:\n{}", output);
+    assert!(output.contains("    Zeroed files: 1\n"), "got:\n{}", output);
+}
+
+#[test]
+fn reported_along_with_hash_mismatch() {
+    let (orig, backup) = zeroed_copy();
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    let assert = cmd().args([&a, &b, "--all", "--check-zeroed"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("DIFFERENT-FILE [HASH]:"), "got:\n{}", output);
+    assert!(output.contains("ZEROED:"), "got:\n{}", output);
+    assert!(output.contains("    Different: 1 "), "got:\n{}", output);
+}
+
+#[test]
+fn zeros_in_the_original_are_fine() {
+    let (_, backup) = zeroed_copy();
+    let (_tmp, a, b) = setup_file_pair("f.bin", &backup, &backup);
+    let assert = cmd().args([&a, &b, "--check-zeroed"]).assert().success();
+    assert!(!stdout_of(&assert).contains("ZEROED:"));
+}
+
+#[test]
+fn shorter_runs_are_ignored() {
+    let orig = vec![b'a'; 256 << 10];
+    let mut backup = orig.clone();
+    backup[1000..2000].fill(0);
+    let (_tmp, a, b) = setup_file_pair("f.bin", &orig, &backup);
+
+    cmd().args([&a, &b, "--check-zeroed"]).assert().success();
+    let assert = cmd().args([&a, &b, "--check-zeroed", "--zeroed-min", "1000"]).assert().code(1);
+    assert!(stdout_of(&assert).contains("1 zero-filled ranges (1000 bytes): 1000..2000\n"));
+}
+
+#[test]
+fn same_zero_run_in_compressed_file_is_fine() {
+    // The file type doesn't matter when the original has the same zeros.
+    let (_, backup) = zeroed_copy();
+    let (_tmp, a, b) = setup_file_pair("f.gz", &backup, &backup);
+    let assert = cmd().args([&a, &b, "--check-zeroed"]).assert().success();
+    let output = stdout_of(&assert);
+    assert!(!output.contains("ZEROED:"), "got:\n{}", output);
+}
+
+#[test]
+fn compressed_files_reported_if_original_unreadable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (orig, backup) = zeroed_copy();
+    let (_tmp, a, b) = setup_file_pair("f.gz", &orig, &backup);
+    let orig_file = format!("{}/f.gz", a);
+    std::fs::set_permissions(&orig_file, std::fs::Permissions::from_mode(0o000)).unwrap();
+    if std::fs::read(&orig_file).is_ok() {
+        // Running as root, which can read it anyway.
+        return;
+    }
+
+    let assert = cmd().args([&a, &b, "--check-zeroed"]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(
+        output.contains("65536..131072 (original unreadable, unexpected in a .gz file)\n"),
+        "got:\n{}",
+        output
+    );
+}
+
+#[test]
+fn manifest_replaces_reading_the_original() {
+    let (orig, backup) = zeroed_copy();
+    let (tmp, a, b) = setup_file_pair("f.bin", &orig, &orig);
+    let manifest = tmp.path().join("manifest.toml");
+    let manifest = manifest.to_str().unwrap();
+    cmd()
+        .args([&a, &b, "--all", "--write-manifest", manifest, "--chunk-size", "16384"])
+        .assert()
+        .success();
+
+    // Zero both sides, keeping the original's modification time: only the
+    // manifest still knows what the original had there.
+    let orig_file = format!("{}/f.bin", a);
+    overwrite_keeping_mtime(&orig_file, &backup);
+    std::fs::write(format!("{}/f.bin", b), &backup).unwrap();
+
+    cmd().args([&a, &b, "--check-zeroed"]).assert().success();
+    let assert = cmd().args([&a, &b, "--check-zeroed", "--manifest", manifest]).assert().code(1);
+    let output = stdout_of(&assert);
+    assert!(output.contains("1 zero-filled ranges (65536 bytes): 65536..131072\n"), "got:\n{}", output);
+}